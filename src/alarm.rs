@@ -0,0 +1,456 @@
+use std::{
+    sync::mpsc::{channel, Receiver, Sender},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+use crate::{
+    battery_module::BatteryField,
+    battery_state::BatteryState,
+    error::{Error, Result},
+};
+
+/// Value an `AlarmRule` watches
+/// Serialized in snake case, e.g. `"cell_voltage_spread"`
+#[derive(Clone, Copy, Debug, Display, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlarmMetric {
+    /// Unit: mV
+    MinCellVoltage,
+    /// Unit: mV
+    MaxCellVoltage,
+    /// Difference between the highest and the lowest cell voltage
+    /// Unit: mV
+    CellVoltageSpread,
+    /// Unit: degC
+    Temperature,
+    /// Unit: %
+    RelativeStateOfCharge,
+    /// Unit: %
+    AbsoluteStateOfCharge,
+    /// Unit: %
+    StateOfHealth,
+    /// Positive while charging
+    /// Unit: mA
+    Current,
+    /// Current flowing out of the module, zero while charging
+    /// Unit: mA
+    DischargeCurrent,
+    /// Current flowing into the module, zero while discharging
+    /// Unit: mA
+    ChargeCurrent,
+    /// Unit: mV
+    BmVoltage,
+}
+
+impl AlarmMetric {
+    /// Returns the value of the metric in `state`
+    pub fn value(&self, state: &(impl BatteryState + ?Sized)) -> Result<f64> {
+        let cell_voltages = || -> Result<(u32, u32)> {
+            let voltages = state.cell_voltages()?;
+            let min = voltages.iter().copied().min();
+            let max = voltages.iter().copied().max();
+            min.zip(max)
+                .ok_or(Error::FieldNotAvailable(BatteryField::CellVoltages))
+        };
+        Ok(match self {
+            AlarmMetric::MinCellVoltage => cell_voltages()?.0 as f64,
+            AlarmMetric::MaxCellVoltage => cell_voltages()?.1 as f64,
+            AlarmMetric::CellVoltageSpread => {
+                let (min, max) = cell_voltages()?;
+                (max - min) as f64
+            }
+            AlarmMetric::Temperature => state.temperature()?,
+            AlarmMetric::RelativeStateOfCharge => state.relative_state_of_charge()? as f64,
+            AlarmMetric::AbsoluteStateOfCharge => state.absolute_state_of_charge()? as f64,
+            AlarmMetric::StateOfHealth => state.state_of_health()? as f64,
+            AlarmMetric::Current => state.current()? as f64,
+            AlarmMetric::DischargeCurrent => (-state.current()?).max(0) as f64,
+            AlarmMetric::ChargeCurrent => state.current()?.max(0) as f64,
+            AlarmMetric::BmVoltage => state.bm_voltage()? as f64,
+        })
+    }
+}
+
+/// When an `AlarmRule` trips
+/// Serialized in snake case, e.g. `{"below": 3100.0}`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlarmCondition {
+    /// Trips when the value is above the threshold
+    Above(f64),
+    /// Trips when the value is below the threshold
+    Below(f64),
+}
+
+impl AlarmCondition {
+    fn is_tripped(&self, value: f64) -> bool {
+        match *self {
+            AlarmCondition::Above(threshold) => value > threshold,
+            AlarmCondition::Below(threshold) => value < threshold,
+        }
+    }
+
+    fn is_cleared(&self, value: f64, hysteresis: f64) -> bool {
+        match *self {
+            AlarmCondition::Above(threshold) => value <= threshold - hysteresis,
+            AlarmCondition::Below(threshold) => value >= threshold + hysteresis,
+        }
+    }
+}
+
+/// A threshold on an `AlarmMetric`
+///
+/// ```
+/// use std::time::Duration;
+/// use fortelion::alarm::*;
+///
+/// // raised after the lowest cell is below 3100 mV for 5 s, cleared above 3150 mV
+/// let rule = AlarmRule::new("low_cell", AlarmMetric::MinCellVoltage, AlarmCondition::Below(3100.0))
+///     .with_hysteresis(50.0)
+///     .with_min_duration(Duration::from_secs(5));
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlarmRule {
+    pub name: String,
+    pub metric: AlarmMetric,
+    pub condition: AlarmCondition,
+    /// How far the value must be back past the threshold to clear the alarm
+    /// Unit: the unit of `metric`
+    #[serde(default)]
+    pub hysteresis: f64,
+    /// How long the condition must hold before the alarm is raised
+    #[serde(default)]
+    pub min_duration: Duration,
+    /// Latched alarms are only cleared after being acknowledged
+    #[serde(default)]
+    pub latching: bool,
+}
+
+impl AlarmRule {
+    pub fn new(name: impl Into<String>, metric: AlarmMetric, condition: AlarmCondition) -> Self {
+        Self {
+            name: name.into(),
+            metric,
+            condition,
+            hysteresis: 0.0,
+            min_duration: Duration::ZERO,
+            latching: false,
+        }
+    }
+
+    pub fn with_hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    pub fn with_min_duration(mut self, min_duration: Duration) -> Self {
+        self.min_duration = min_duration;
+        self
+    }
+
+    pub fn latching(mut self) -> Self {
+        self.latching = true;
+        self
+    }
+}
+
+/// Serialized in snake case, e.g. `"raised"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlarmTransition {
+    Raised,
+    Cleared,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlarmEvent {
+    /// Name of the rule
+    pub rule: String,
+    pub transition: AlarmTransition,
+    /// Value of the metric when the alarm was raised or cleared, `None` if cleared by acknowledging
+    pub value: Option<f64>,
+    pub at: SystemTime,
+}
+
+#[derive(Clone, Debug, Default)]
+struct RuleState {
+    active: bool,
+    tripped_since: Option<SystemTime>,
+    // latched alarm whose condition has cleared
+    clearable: bool,
+    acknowledged: bool,
+}
+
+/// Evaluates `AlarmRule`s against successive readings
+///
+/// Raised and cleared alarms are sent to every receiver returned by `subscribe`.
+/// Rules whose metric cannot be read from a reading are left as they are.
+pub struct AlarmEngine {
+    rules: Vec<(AlarmRule, RuleState)>,
+    subscribers: Vec<Sender<AlarmEvent>>,
+}
+
+impl AlarmEngine {
+    pub fn new(rules: impl IntoIterator<Item = AlarmRule>) -> Self {
+        Self {
+            rules: rules
+                .into_iter()
+                .map(|rule| (rule, RuleState::default()))
+                .collect(),
+            subscribers: vec![],
+        }
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &AlarmRule> {
+        self.rules.iter().map(|(rule, _)| rule)
+    }
+
+    /// Returns a receiver of the events from now on
+    pub fn subscribe(&mut self) -> Receiver<AlarmEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Evaluates the rules against a reading taken at `at`
+    pub fn evaluate(&mut self, state: &(impl BatteryState + ?Sized), at: SystemTime) {
+        let mut events = vec![];
+        for (rule, rule_state) in &mut self.rules {
+            let value = match rule.metric.value(state) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            let tripped = rule.condition.is_tripped(value);
+            if !rule_state.active {
+                if !tripped {
+                    rule_state.tripped_since = None;
+                    continue;
+                }
+                let since = *rule_state.tripped_since.get_or_insert(at);
+                if at.duration_since(since).unwrap_or_default() >= rule.min_duration {
+                    rule_state.active = true;
+                    rule_state.clearable = false;
+                    rule_state.acknowledged = false;
+                    events.push(AlarmEvent {
+                        rule: rule.name.clone(),
+                        transition: AlarmTransition::Raised,
+                        value: Some(value),
+                        at,
+                    });
+                }
+            } else if rule.condition.is_cleared(value, rule.hysteresis) {
+                if rule.latching && !rule_state.acknowledged {
+                    rule_state.clearable = true;
+                    continue;
+                }
+                *rule_state = RuleState::default();
+                events.push(AlarmEvent {
+                    rule: rule.name.clone(),
+                    transition: AlarmTransition::Cleared,
+                    value: Some(value),
+                    at,
+                });
+            } else if tripped {
+                rule_state.clearable = false;
+            }
+        }
+        for event in events {
+            self.send(event);
+        }
+    }
+
+    /// Evaluates the rules against a reading taken now
+    pub fn evaluate_now(&mut self, state: &(impl BatteryState + ?Sized)) {
+        self.evaluate(state, SystemTime::now());
+    }
+
+    /// Acknowledges a latched alarm
+    /// The alarm is cleared now if its condition has cleared, otherwise as soon as it clears.
+    pub fn acknowledge(&mut self, name: &str, at: SystemTime) {
+        let mut events = vec![];
+        for (rule, rule_state) in &mut self.rules {
+            if rule.name != name || !rule_state.active {
+                continue;
+            }
+            if rule_state.clearable {
+                *rule_state = RuleState::default();
+                events.push(AlarmEvent {
+                    rule: rule.name.clone(),
+                    transition: AlarmTransition::Cleared,
+                    value: None,
+                    at,
+                });
+            } else {
+                rule_state.acknowledged = true;
+            }
+        }
+        for event in events {
+            self.send(event);
+        }
+    }
+
+    /// Returns the names of the rules whose alarm is raised
+    pub fn active_alarms(&self) -> Vec<&str> {
+        self.rules
+            .iter()
+            .filter(|(_, rule_state)| rule_state.active)
+            .map(|(rule, _)| rule.name.as_str())
+            .collect()
+    }
+
+    fn send(&mut self, event: AlarmEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn cells(min: u32) -> BatterySnapshot {
+        BatterySnapshot {
+            cell_voltages: Some(vec![min, 3300, 3310, 3300, 3300, 3300, 3300, 3300]),
+            ..Default::default()
+        }
+    }
+
+    fn transitions(receiver: &Receiver<AlarmEvent>) -> Vec<(String, AlarmTransition)> {
+        receiver
+            .try_iter()
+            .map(|event| (event.rule, event.transition))
+            .collect()
+    }
+
+    #[test]
+    fn test_metric() {
+        let state = BatterySnapshot {
+            current: Some(-12000),
+            ..cells(3200)
+        };
+        assert_eq!(AlarmMetric::MinCellVoltage.value(&state).unwrap(), 3200.0);
+        assert_eq!(AlarmMetric::CellVoltageSpread.value(&state).unwrap(), 110.0);
+        assert_eq!(
+            AlarmMetric::DischargeCurrent.value(&state).unwrap(),
+            12000.0
+        );
+        assert_eq!(AlarmMetric::ChargeCurrent.value(&state).unwrap(), 0.0);
+        assert!(AlarmMetric::Temperature.value(&state).is_err());
+
+        let state = BatterySnapshot {
+            cell_voltages: Some(vec![]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            AlarmMetric::MinCellVoltage.value(&state),
+            Err(Error::FieldNotAvailable(BatteryField::CellVoltages))
+        ));
+    }
+
+    #[test]
+    fn test_hysteresis_and_min_duration() {
+        let rule = AlarmRule::new(
+            "low_cell",
+            AlarmMetric::MinCellVoltage,
+            AlarmCondition::Below(3100.0),
+        )
+        .with_hysteresis(50.0)
+        .with_min_duration(Duration::from_secs(5));
+        let mut engine = AlarmEngine::new([rule]);
+        let receiver = engine.subscribe();
+
+        engine.evaluate(&cells(3050), at(0));
+        engine.evaluate(&cells(3050), at(3));
+        engine.evaluate(&cells(3200), at(4));
+        engine.evaluate(&cells(3050), at(5));
+        assert!(transitions(&receiver).is_empty());
+
+        engine.evaluate(&cells(3050), at(10));
+        assert_eq!(
+            transitions(&receiver),
+            vec![("low_cell".to_owned(), AlarmTransition::Raised)]
+        );
+        assert_eq!(engine.active_alarms(), vec!["low_cell"]);
+
+        // within hysteresis
+        engine.evaluate(&cells(3120), at(11));
+        assert!(transitions(&receiver).is_empty());
+
+        // not readable
+        engine.evaluate(&BatterySnapshot::default(), at(12));
+        assert!(transitions(&receiver).is_empty());
+
+        engine.evaluate(&cells(3150), at(13));
+        assert_eq!(
+            transitions(&receiver),
+            vec![("low_cell".to_owned(), AlarmTransition::Cleared)]
+        );
+        assert!(engine.active_alarms().is_empty());
+    }
+
+    #[test]
+    fn test_latching() {
+        let rule = AlarmRule::new(
+            "over_temperature",
+            AlarmMetric::Temperature,
+            AlarmCondition::Above(45.0),
+        )
+        .latching();
+        let mut engine = AlarmEngine::new([rule]);
+        let receiver = engine.subscribe();
+        let temperature = |temperature| BatterySnapshot {
+            temperature: Some(temperature),
+            ..Default::default()
+        };
+
+        engine.evaluate(&temperature(46.0), at(0));
+        engine.evaluate(&temperature(30.0), at(1));
+        assert_eq!(
+            transitions(&receiver),
+            vec![("over_temperature".to_owned(), AlarmTransition::Raised)]
+        );
+
+        engine.acknowledge("over_temperature", at(2));
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].transition, AlarmTransition::Cleared);
+        assert_eq!(events[0].value, None);
+
+        // acknowledged while the condition holds
+        engine.evaluate(&temperature(46.0), at(3));
+        engine.acknowledge("over_temperature", at(4));
+        assert_eq!(engine.active_alarms(), vec!["over_temperature"]);
+        engine.evaluate(&temperature(30.0), at(5));
+        assert_eq!(
+            transitions(&receiver),
+            vec![
+                ("over_temperature".to_owned(), AlarmTransition::Raised),
+                ("over_temperature".to_owned(), AlarmTransition::Cleared)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serde() {
+        let rule: AlarmRule = serde_json::from_str(
+            r#"{"name": "high_current", "metric": "discharge_current", "condition": {"above": 60000.0}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rule,
+            AlarmRule::new(
+                "high_current",
+                AlarmMetric::DischargeCurrent,
+                AlarmCondition::Above(60000.0)
+            )
+        );
+    }
+}
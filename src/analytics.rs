@@ -0,0 +1,16 @@
+mod anomaly;
+mod cell_balance;
+mod cycles;
+mod energy;
+mod health_trend;
+mod regression;
+mod resistance;
+mod runtime;
+
+pub use anomaly::{Anomaly, AnomalyDetector, AnomalyKind};
+pub use cell_balance::{CellBalanceTracker, CellDrift, CellStatistics};
+pub use cycles::{CycleCounter, DEPTH_BINS};
+pub use energy::{EnergyAccumulator, EnergyTotals};
+pub use health_trend::{DailyHealth, FadeFit, FadeModel, HealthMetric, HealthTrendTracker};
+pub use resistance::{ResistanceEstimate, ResistanceEstimator};
+pub use runtime::{LoadProfile, RuntimeEstimate, RuntimeEstimator, RuntimeKind, Smoothing};
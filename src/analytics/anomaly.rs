@@ -0,0 +1,354 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::battery_state::BatteryState;
+
+/// Resolution of the temperature reading
+/// Unit: degC
+const TEMPERATURE_RESOLUTION: f64 = 0.1;
+
+/// Serialized in snake case, e.g. `"frozen_current"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// The current stays exactly the same while the robot is moving
+    FrozenCurrent,
+    /// The temperature changes faster than a module can heat or cool
+    TemperatureJump,
+    /// The state of charge changes without matching charge flow
+    StateOfChargeJump,
+    /// No cell voltage changes while current flows
+    FrozenCellVoltages,
+}
+
+/// A finding of `AnomalyDetector`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    /// How far the readings are past the limit, 1.0 at the limit
+    pub score: f64,
+    pub explanation: String,
+    pub at: SystemTime,
+}
+
+#[derive(Clone, Debug)]
+struct Sample {
+    at: SystemTime,
+    /// Unit: mA
+    current: Option<i32>,
+    /// Unit: degC
+    temperature: Option<f64>,
+    /// Unit: %
+    relative_state_of_charge: Option<u32>,
+    /// Unit: mAh
+    full_charge_capacity: Option<u32>,
+}
+
+/// Looks for stuck sensors and implausible jumps in successive readings
+///
+/// Frozen values are reported on every reading once they have lasted the minimum
+/// frozen duration, with a score growing with their duration. Jumps are reported
+/// on the reading where they happen. Readings further apart than the maximum gap
+/// are not compared.
+#[derive(Clone, Debug)]
+pub struct AnomalyDetector {
+    min_frozen_duration: Duration,
+    max_temperature_rate: f64,
+    state_of_charge_tolerance: f64,
+    min_current: u32,
+    max_gap: Duration,
+    moving: bool,
+    last: Option<Sample>,
+    // frozen value and since when
+    frozen_current: Option<(i32, SystemTime)>,
+    frozen_cell_voltages: Option<(Vec<u32>, SystemTime)>,
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnomalyDetector {
+    pub fn new() -> Self {
+        Self {
+            min_frozen_duration: Duration::from_secs(60),
+            max_temperature_rate: 0.5,
+            state_of_charge_tolerance: 3.0,
+            min_current: 1000,
+            max_gap: Duration::from_secs(60),
+            moving: false,
+            last: None,
+            frozen_current: None,
+            frozen_cell_voltages: None,
+        }
+    }
+
+    /// Sets how long a value must stay the same to be frozen (default: 60 s)
+    pub fn with_min_frozen_duration(mut self, duration: Duration) -> Self {
+        self.min_frozen_duration = duration;
+        self
+    }
+
+    /// Sets the fastest plausible change of the temperature (default: 0.5)
+    /// Unit: degC/s
+    pub fn with_max_temperature_rate(mut self, rate: f64) -> Self {
+        self.max_temperature_rate = rate;
+        self
+    }
+
+    /// Sets how far a change of the state of charge may be from the charge flow (default: 3)
+    /// Unit: %
+    pub fn with_state_of_charge_tolerance(mut self, tolerance: f64) -> Self {
+        self.state_of_charge_tolerance = tolerance;
+        self
+    }
+
+    /// Sets the magnitude of the current above which cell voltages must move (default: 1000)
+    /// Unit: mA
+    pub fn with_min_current(mut self, current: u32) -> Self {
+        self.min_current = current;
+        self
+    }
+
+    /// Sets the longest interval between readings which are compared (default: 60 s)
+    pub fn with_max_gap(mut self, max_gap: Duration) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Tells whether the robot is moving, which the current is expected to follow
+    pub fn set_moving(&mut self, moving: bool) {
+        if moving != self.moving {
+            self.frozen_current = None;
+        }
+        self.moving = moving;
+    }
+
+    /// Checks the readable fields of `state` read at `at`
+    /// Returns the anomalies found in this reading.
+    pub fn update(&mut self, state: &(impl BatteryState + ?Sized), at: SystemTime) -> Vec<Anomaly> {
+        let sample = Sample {
+            at,
+            current: state.current().ok(),
+            temperature: state.temperature().ok(),
+            relative_state_of_charge: state.relative_state_of_charge().ok(),
+            full_charge_capacity: state.full_charge_capacity().ok(),
+        };
+        let cell_voltages = state.cell_voltages().ok();
+
+        let mut anomalies = vec![];
+        let last = self.last.take().filter(|last| {
+            at.duration_since(last.at)
+                .is_ok_and(|interval| interval <= self.max_gap)
+        });
+        if last.is_none() {
+            self.frozen_current = None;
+            self.frozen_cell_voltages = None;
+        }
+        anomalies.extend(self.check_frozen_current(&sample));
+        anomalies.extend(self.check_frozen_cell_voltages(&sample, cell_voltages));
+        if let Some(last) = &last {
+            anomalies.extend(self.check_temperature(last, &sample));
+            anomalies.extend(self.check_state_of_charge(last, &sample));
+        }
+        self.last = Some(sample);
+        anomalies
+    }
+
+    /// Forgets the previous readings
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.frozen_current = None;
+        self.frozen_cell_voltages = None;
+    }
+
+    fn check_frozen_current(&mut self, sample: &Sample) -> Option<Anomaly> {
+        let Some(current) = sample.current.filter(|_| self.moving) else {
+            self.frozen_current = None;
+            return None;
+        };
+        let since = match self.frozen_current {
+            Some((frozen, since)) if frozen == current => since,
+            _ => {
+                self.frozen_current = Some((current, sample.at));
+                sample.at
+            }
+        };
+        let frozen_for = sample.at.duration_since(since).unwrap_or_default();
+        (frozen_for >= self.min_frozen_duration).then(|| Anomaly {
+            kind: AnomalyKind::FrozenCurrent,
+            score: frozen_for.as_secs_f64() / self.min_frozen_duration.as_secs_f64(),
+            explanation: format!(
+                "current stuck at {current} mA for {:.0} s while moving",
+                frozen_for.as_secs_f64()
+            ),
+            at: sample.at,
+        })
+    }
+
+    fn check_frozen_cell_voltages(
+        &mut self,
+        sample: &Sample,
+        cell_voltages: Option<Vec<u32>>,
+    ) -> Option<Anomaly> {
+        let flowing = sample
+            .current
+            .is_some_and(|current| current.unsigned_abs() >= self.min_current);
+        let Some(cell_voltages) = cell_voltages.filter(|_| flowing) else {
+            self.frozen_cell_voltages = None;
+            return None;
+        };
+        let since = match &self.frozen_cell_voltages {
+            Some((frozen, since)) if *frozen == cell_voltages => *since,
+            _ => {
+                self.frozen_cell_voltages = Some((cell_voltages, sample.at));
+                sample.at
+            }
+        };
+        let frozen_for = sample.at.duration_since(since).unwrap_or_default();
+        (frozen_for >= self.min_frozen_duration).then(|| Anomaly {
+            kind: AnomalyKind::FrozenCellVoltages,
+            score: frozen_for.as_secs_f64() / self.min_frozen_duration.as_secs_f64(),
+            explanation: format!(
+                "cell voltages unchanged for {:.0} s while {} mA flows",
+                frozen_for.as_secs_f64(),
+                sample.current.unwrap_or_default()
+            ),
+            at: sample.at,
+        })
+    }
+
+    fn check_temperature(&self, last: &Sample, sample: &Sample) -> Option<Anomaly> {
+        let (from, to) = (last.temperature?, sample.temperature?);
+        let interval = sample.at.duration_since(last.at).ok()?.as_secs_f64();
+        let change = (to - from).abs();
+        let allowed = self.max_temperature_rate * interval + TEMPERATURE_RESOLUTION;
+        (change > allowed + 1e-9).then(|| Anomaly {
+            kind: AnomalyKind::TemperatureJump,
+            score: change / allowed,
+            explanation: format!(
+                "temperature jumped from {from:.1} to {to:.1} degC in {interval:.1} s"
+            ),
+            at: sample.at,
+        })
+    }
+
+    fn check_state_of_charge(&self, last: &Sample, sample: &Sample) -> Option<Anomaly> {
+        let (from, to) = (
+            last.relative_state_of_charge?,
+            sample.relative_state_of_charge?,
+        );
+        let full_charge_capacity = sample
+            .full_charge_capacity
+            .filter(|capacity| *capacity > 0)?;
+        let hours = sample.at.duration_since(last.at).ok()?.as_secs_f64() / 3600.0;
+        // trapezoidal charge flow, in mAh
+        let charge = (last.current? as f64 + sample.current? as f64) / 2.0 * hours;
+        let expected = charge / full_charge_capacity as f64 * 100.0;
+        let change = to as f64 - from as f64;
+        let deviation = (change - expected).abs();
+        (deviation > self.state_of_charge_tolerance).then(|| Anomaly {
+            kind: AnomalyKind::StateOfChargeJump,
+            score: deviation / self.state_of_charge_tolerance,
+            explanation: format!(
+                "state of charge changed from {from} to {to} % while charge flow accounts for {expected:+.1} %"
+            ),
+            at: sample.at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn kinds(anomalies: &[Anomaly]) -> Vec<AnomalyKind> {
+        anomalies.iter().map(|anomaly| anomaly.kind).collect()
+    }
+
+    #[test]
+    fn test_frozen_current() {
+        let mut detector = AnomalyDetector::new();
+        let state = BatterySnapshot {
+            current: Some(-5000),
+            ..Default::default()
+        };
+        // not moving, nothing expected
+        for secs in (0..=120).step_by(10) {
+            assert!(detector.update(&state, at(secs)).is_empty());
+        }
+        detector.set_moving(true);
+        let mut found = vec![];
+        for secs in (130..=250).step_by(10) {
+            found = detector.update(&state, at(secs));
+        }
+        assert_eq!(kinds(&found), vec![AnomalyKind::FrozenCurrent]);
+        assert!((found[0].score - 2.0).abs() < 1e-9);
+        assert_eq!(
+            found[0].explanation,
+            "current stuck at -5000 mA for 120 s while moving"
+        );
+    }
+
+    #[test]
+    fn test_temperature_jump() {
+        let mut detector = AnomalyDetector::new();
+        let mut state = BatterySnapshot {
+            temperature: Some(25.0),
+            ..Default::default()
+        };
+        detector.update(&state, at(0));
+        state.temperature = Some(25.5);
+        assert!(detector.update(&state, at(1)).is_empty());
+        state.temperature = Some(45.5);
+        let found = detector.update(&state, at(2));
+        assert_eq!(kinds(&found), vec![AnomalyKind::TemperatureJump]);
+        assert!((found[0].score - 20.0 / 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_state_of_charge_jump() {
+        let mut detector = AnomalyDetector::new();
+        let mut state = BatterySnapshot {
+            current: Some(-10000),
+            relative_state_of_charge: Some(50),
+            full_charge_capacity: Some(10000),
+            ..Default::default()
+        };
+        detector.update(&state, at(0));
+        // 10 A for 36 s is 1 % of 10 Ah
+        state.relative_state_of_charge = Some(49);
+        assert!(detector.update(&state, at(36)).is_empty());
+        state.relative_state_of_charge = Some(80);
+        let found = detector.update(&state, at(72));
+        assert_eq!(kinds(&found), vec![AnomalyKind::StateOfChargeJump]);
+        assert!((found[0].score - 32.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_frozen_cell_voltages() {
+        let mut detector = AnomalyDetector::new().with_min_frozen_duration(Duration::from_secs(30));
+        let mut state = BatterySnapshot {
+            current: Some(-20000),
+            cell_voltages: Some(vec![3300; 8]),
+            ..Default::default()
+        };
+        let mut found = vec![];
+        for secs in 0..=30 {
+            found = detector.update(&state, at(secs));
+        }
+        assert_eq!(kinds(&found), vec![AnomalyKind::FrozenCellVoltages]);
+
+        // no current, no expectation
+        state.current = Some(0);
+        assert!(detector.update(&state, at(31)).is_empty());
+    }
+}
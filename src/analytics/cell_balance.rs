@@ -0,0 +1,282 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::regression::linear_regression;
+use crate::{battery_state::BatteryState, error::Result};
+
+/// Statistics of the cell voltages in one sample
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CellStatistics {
+    /// Unit: mV
+    pub min: u32,
+    /// Unit: mV
+    pub max: u32,
+    /// Unit: mV
+    pub mean: f64,
+    /// Difference between `max` and `min`
+    /// Unit: mV
+    pub spread: u32,
+    /// Population standard deviation
+    /// Unit: mV
+    pub standard_deviation: f64,
+    /// Index of the cell with the lowest voltage
+    pub min_cell: usize,
+    /// Index of the cell with the highest voltage
+    pub max_cell: usize,
+}
+
+impl CellStatistics {
+    /// Returns `None` if `cell_voltages` is empty
+    pub fn from_voltages(cell_voltages: &[u32]) -> Option<Self> {
+        let (min_cell, &min) = cell_voltages
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, voltage)| **voltage)?;
+        let (max_cell, &max) = cell_voltages
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, voltage)| **voltage)?;
+        let n = cell_voltages.len() as f64;
+        let mean = cell_voltages.iter().map(|v| *v as f64).sum::<f64>() / n;
+        let variance = cell_voltages
+            .iter()
+            .map(|v| (*v as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        Some(Self {
+            min,
+            max,
+            mean,
+            spread: max - min,
+            standard_deviation: variance.sqrt(),
+            min_cell,
+            max_cell,
+        })
+    }
+}
+
+/// A cell whose deviation from the mean grows
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CellDrift {
+    pub cell: usize,
+    /// Latest deviation from the mean, negative when the cell is lower
+    /// Unit: mV
+    pub deviation: f64,
+    /// How fast the absolute deviation grows
+    /// Unit: mV/h
+    pub rate: f64,
+}
+
+/// Tracks cell balance over successive samples
+///
+/// Counts how often each cell is the lowest, and fits the deviation of each cell
+/// from the mean over the latest samples to find cells drifting away.
+#[derive(Clone, Debug)]
+pub struct CellBalanceTracker {
+    window: usize,
+    drift_threshold: f64,
+    samples: u64,
+    lowest_counts: Vec<u64>,
+    // (time, deviation of each cell)
+    history: VecDeque<(SystemTime, Vec<f64>)>,
+    latest: Option<CellStatistics>,
+}
+
+impl Default for CellBalanceTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CellBalanceTracker {
+    pub fn new() -> Self {
+        Self {
+            window: 100,
+            drift_threshold: 1.0,
+            samples: 0,
+            lowest_counts: vec![],
+            history: VecDeque::new(),
+            latest: None,
+        }
+    }
+
+    /// Sets the number of latest samples the drift is fitted over (default: 100)
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(2);
+        self
+    }
+
+    /// Sets the growth rate of the deviation above which a cell is drifting (default: 1.0)
+    /// Unit: mV/h
+    pub fn with_drift_threshold(mut self, drift_threshold: f64) -> Self {
+        self.drift_threshold = drift_threshold;
+        self
+    }
+
+    /// Feeds the cell voltages of `state` read at `at`
+    pub fn update(
+        &mut self,
+        state: &(impl BatteryState + ?Sized),
+        at: SystemTime,
+    ) -> Result<Option<CellStatistics>> {
+        let cell_voltages = state.cell_voltages()?;
+        Ok(self.update_voltages(&cell_voltages, at))
+    }
+
+    /// Feeds cell voltages read at `at`
+    /// Unit: mV
+    pub fn update_voltages(
+        &mut self,
+        cell_voltages: &[u32],
+        at: SystemTime,
+    ) -> Option<CellStatistics> {
+        let statistics = CellStatistics::from_voltages(cell_voltages)?;
+        if self.lowest_counts.len() != cell_voltages.len() {
+            self.reset();
+            self.lowest_counts = vec![0; cell_voltages.len()];
+        }
+        self.samples += 1;
+        self.lowest_counts[statistics.min_cell] += 1;
+
+        let deviations = cell_voltages
+            .iter()
+            .map(|v| *v as f64 - statistics.mean)
+            .collect();
+        self.history.push_back((at, deviations));
+        while self.history.len() > self.window {
+            self.history.pop_front();
+        }
+        self.latest = Some(statistics.clone());
+        Some(statistics)
+    }
+
+    /// Returns the statistics of the latest sample
+    pub fn latest(&self) -> Option<&CellStatistics> {
+        self.latest.as_ref()
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Returns how many times each cell was the lowest
+    pub fn lowest_counts(&self) -> &[u64] {
+        &self.lowest_counts
+    }
+
+    /// Returns the cell which was the lowest most often
+    pub fn weakest_cell(&self) -> Option<usize> {
+        self.lowest_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .max_by_key(|(cell, count)| (**count, std::cmp::Reverse(*cell)))
+            .map(|(cell, _)| cell)
+    }
+
+    /// Returns the growth rate of the absolute deviation of each cell over the window
+    /// Unit: mV/h
+    pub fn drift_rates(&self) -> Vec<Option<f64>> {
+        let Some((start, _)) = self.history.front() else {
+            return vec![];
+        };
+        (0..self.lowest_counts.len())
+            .map(|cell| {
+                linear_regression(self.history.iter().map(|(at, deviations)| {
+                    let hours =
+                        at.duration_since(*start).unwrap_or_default().as_secs_f64() / 3600.0;
+                    (hours, deviations[cell].abs())
+                }))
+                .map(|(slope, _)| slope)
+            })
+            .collect()
+    }
+
+    /// Returns the cells whose deviation grows faster than the drift threshold, fastest first
+    pub fn drifting_cells(&self) -> Vec<CellDrift> {
+        let Some((_, latest)) = self.history.back() else {
+            return vec![];
+        };
+        let mut drifts: Vec<_> = self
+            .drift_rates()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(cell, rate)| {
+                let rate = rate?;
+                (rate > self.drift_threshold).then(|| CellDrift {
+                    cell,
+                    deviation: latest[cell],
+                    rate,
+                })
+            })
+            .collect();
+        drifts.sort_by(|a, b| b.rate.total_cmp(&a.rate));
+        drifts
+    }
+
+    pub fn reset(&mut self) {
+        self.samples = 0;
+        self.lowest_counts.clear();
+        self.history.clear();
+        self.latest = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn at(hours: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(hours * 3600)
+    }
+
+    #[test]
+    fn test_statistics() {
+        let statistics =
+            CellStatistics::from_voltages(&[3300, 3310, 3290, 3300, 3300, 3300, 3300, 3300])
+                .unwrap();
+        assert_eq!(statistics.min, 3290);
+        assert_eq!(statistics.max, 3310);
+        assert_eq!(statistics.spread, 20);
+        assert_eq!(statistics.mean, 3300.0);
+        assert!((statistics.standard_deviation - 5.0).abs() < 1e-9);
+        assert_eq!(statistics.min_cell, 2);
+        assert_eq!(statistics.max_cell, 1);
+        assert_eq!(CellStatistics::from_voltages(&[]), None);
+    }
+
+    #[test]
+    fn test_weakest_cell() {
+        let mut tracker = CellBalanceTracker::new();
+        tracker.update_voltages(&[3300, 3290, 3300, 3300], at(0));
+        tracker.update_voltages(&[3300, 3300, 3280, 3300], at(1));
+        tracker.update_voltages(&[3300, 3290, 3300, 3300], at(2));
+        assert_eq!(tracker.lowest_counts(), &[0, 2, 1, 0]);
+        assert_eq!(tracker.weakest_cell(), Some(1));
+        assert_eq!(tracker.samples(), 3);
+    }
+
+    #[test]
+    fn test_drifting_cell() {
+        let mut tracker = CellBalanceTracker::new().with_drift_threshold(1.5);
+        for hour in 0..10 {
+            let state = BatterySnapshot {
+                cell_voltages: Some(vec![3300, 3300, 3300 - 4 * hour as u32, 3300]),
+                ..Default::default()
+            };
+            tracker.update(&state, at(hour)).unwrap();
+        }
+        let drifts = tracker.drifting_cells();
+        assert_eq!(drifts[0].cell, 2);
+        assert!((drifts[0].rate - 3.0).abs() < 1e-9);
+        assert!(drifts[0].deviation < 0.0);
+        // the others follow the mean, slower than the threshold
+        assert_eq!(drifts.len(), 1);
+
+        assert!(tracker.update(&BatterySnapshot::default(), at(10)).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{battery_state::BatteryState, error::Result};
+
+/// Number of bins of the depth histogram, each 10 % SoC wide
+pub const DEPTH_BINS: usize = 10;
+
+/// Counts equivalent full cycles and partial cycles of a battery module
+///
+/// Equivalent full cycles are the discharged charge divided by the design capacity.
+/// Partial cycles are counted by rainflow counting over the relative state of charge,
+/// and kept as a histogram of their depth.
+///
+/// The counter is serializable, so its counts can be persisted across restarts.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CycleCounter {
+    max_gap: Duration,
+    /// Unit: mAh
+    discharged_capacity: f64,
+    equivalent_full_cycles: f64,
+    /// (time, current in mA)
+    last_current: Option<(SystemTime, i32)>,
+    last_state_of_charge: Option<f64>,
+    rising: Option<bool>,
+    /// Reversal points not closed as a cycle yet
+    reversals: Vec<f64>,
+    histogram: [f64; DEPTH_BINS],
+}
+
+impl Default for CycleCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CycleCounter {
+    pub fn new() -> Self {
+        Self {
+            max_gap: Duration::from_secs(60),
+            discharged_capacity: 0.0,
+            equivalent_full_cycles: 0.0,
+            last_current: None,
+            last_state_of_charge: None,
+            rising: None,
+            reversals: vec![],
+            histogram: [0.0; DEPTH_BINS],
+        }
+    }
+
+    /// Sets the longest interval between samples which is integrated (default: 60 s)
+    pub fn with_max_gap(mut self, max_gap: Duration) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Feeds `current`, `design_capacity` and, if available, `relative_state_of_charge` of `state` read at `at`
+    pub fn update(&mut self, state: &(impl BatteryState + ?Sized), at: SystemTime) -> Result<()> {
+        let current = state.current()?;
+        let design_capacity = state.design_capacity()?;
+        self.update_current(current, design_capacity, at);
+        if let Ok(state_of_charge) = state.relative_state_of_charge() {
+            self.update_state_of_charge(state_of_charge as f64);
+        }
+        Ok(())
+    }
+
+    /// Feeds a current (mA, positive while charging) read at `at`, and the design capacity (mAh)
+    pub fn update_current(&mut self, current: i32, design_capacity: u32, at: SystemTime) {
+        if let Some((last_at, last_current)) = self.last_current {
+            match at.duration_since(last_at) {
+                Ok(interval) if interval <= self.max_gap => {
+                    let hours = interval.as_secs_f64() / 3600.0;
+                    let discharged =
+                        ((-last_current).max(0) as f64 + (-current).max(0) as f64) / 2.0 * hours;
+                    self.discharged_capacity += discharged;
+                    if design_capacity > 0 {
+                        self.equivalent_full_cycles += discharged / design_capacity as f64;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.last_current = Some((at, current));
+    }
+
+    /// Feeds a state of charge
+    /// Unit: %
+    pub fn update_state_of_charge(&mut self, state_of_charge: f64) {
+        let Some(last) = self.last_state_of_charge else {
+            self.last_state_of_charge = Some(state_of_charge);
+            self.push_reversal(state_of_charge);
+            return;
+        };
+        if state_of_charge == last {
+            return;
+        }
+        let rising = state_of_charge > last;
+        if self.rising.is_some_and(|previous| previous != rising) {
+            self.push_reversal(last);
+        }
+        self.rising = Some(rising);
+        self.last_state_of_charge = Some(state_of_charge);
+    }
+
+    // ASTM E1049 rainflow counting, one reversal at a time
+    fn push_reversal(&mut self, point: f64) {
+        self.reversals.push(point);
+        while self.reversals.len() >= 3 {
+            let n = self.reversals.len();
+            let x = (self.reversals[n - 1] - self.reversals[n - 2]).abs();
+            let y = (self.reversals[n - 2] - self.reversals[n - 3]).abs();
+            if x < y {
+                break;
+            }
+            if n == 3 {
+                self.count(y, 0.5);
+                self.reversals.remove(0);
+            } else {
+                self.count(y, 1.0);
+                self.reversals.drain(n - 3..n - 1);
+            }
+        }
+    }
+
+    fn count(&mut self, depth: f64, cycles: f64) {
+        let bin = ((depth / 100.0 * DEPTH_BINS as f64) as usize).min(DEPTH_BINS - 1);
+        self.histogram[bin] += cycles;
+    }
+
+    /// Unit: mAh
+    pub fn discharged_capacity(&self) -> f64 {
+        self.discharged_capacity
+    }
+
+    pub fn equivalent_full_cycles(&self) -> f64 {
+        self.equivalent_full_cycles
+    }
+
+    /// Returns the number of cycles by depth, in bins 10 % SoC wide
+    /// Cycles which are not closed yet, including the ongoing one, are counted as half cycles.
+    pub fn depth_histogram(&self) -> [f64; DEPTH_BINS] {
+        let mut histogram = self.histogram;
+        let mut points = self.reversals.clone();
+        points.extend(self.last_state_of_charge);
+        for pair in points.windows(2) {
+            let depth = (pair[1] - pair[0]).abs();
+            if depth > 0.0 {
+                let bin = ((depth / 100.0 * DEPTH_BINS as f64) as usize).min(DEPTH_BINS - 1);
+                histogram[bin] += 0.5;
+            }
+        }
+        histogram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_equivalent_full_cycles() {
+        let mut counter = CycleCounter::new();
+        let mut state = BatterySnapshot {
+            current: Some(-36000),
+            design_capacity: Some(10000),
+            ..Default::default()
+        };
+        for secs in (0..=1000).step_by(10) {
+            counter.update(&state, at(secs)).unwrap();
+        }
+        // charging does not count
+        state.current = Some(36000);
+        for secs in (1010..=2000).step_by(10) {
+            counter.update(&state, at(secs)).unwrap();
+        }
+        // 36 A for 1005 s
+        assert!((counter.discharged_capacity() - 10050.0).abs() < 1e-6);
+        assert!((counter.equivalent_full_cycles() - 1.005).abs() < 1e-9);
+
+        assert!(counter
+            .update(&BatterySnapshot::default(), at(2010))
+            .is_err());
+    }
+
+    #[test]
+    fn test_rainflow() {
+        let mut counter = CycleCounter::new();
+        // 100 -> 20 -> 60 -> 40 -> 100 -> 20
+        let mut soc = 100.0;
+        counter.update_state_of_charge(soc);
+        for target in [20.0, 60.0, 40.0, 100.0, 20.0] {
+            while soc != target {
+                soc += if target > soc { 5.0 } else { -5.0 };
+                counter.update_state_of_charge(soc);
+            }
+        }
+        let histogram = counter.depth_histogram();
+        // 60 -> 40 closed as a full cycle
+        assert_eq!(histogram[2], 1.0);
+        // 100 -> 20 -> 100 -> 20 as half cycles
+        assert_eq!(histogram[8], 1.5);
+        assert_eq!(histogram.iter().sum::<f64>(), 2.5);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let mut counter = CycleCounter::new();
+        counter.update_current(-10000, 10000, at(0));
+        counter.update_current(-10000, 10000, at(36));
+        counter.update_state_of_charge(80.0);
+        counter.update_state_of_charge(70.0);
+        let json = serde_json::to_string(&counter).unwrap();
+        let restored: CycleCounter = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, counter);
+        assert!((restored.equivalent_full_cycles() - 0.01).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,253 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{battery_state::BatteryState, error::Result};
+
+/// Energy and charge which went in and out of a battery module
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyTotals {
+    /// Unit: Wh
+    pub charged_energy: f64,
+    /// Unit: Wh
+    pub discharged_energy: f64,
+    /// Unit: Ah
+    pub charged_capacity: f64,
+    /// Unit: Ah
+    pub discharged_capacity: f64,
+    /// Time covered by the samples
+    pub duration: Duration,
+}
+
+impl EnergyTotals {
+    /// Positive when more energy went in than out
+    /// Unit: Wh
+    pub fn net_energy(&self) -> f64 {
+        self.charged_energy - self.discharged_energy
+    }
+
+    /// Positive when more charge went in than out
+    /// Unit: Ah
+    pub fn net_capacity(&self) -> f64 {
+        self.charged_capacity - self.discharged_capacity
+    }
+
+    fn add(&mut self, other: &EnergyTotals) {
+        self.charged_energy += other.charged_energy;
+        self.discharged_energy += other.discharged_energy;
+        self.charged_capacity += other.charged_capacity;
+        self.discharged_capacity += other.discharged_capacity;
+        self.duration += other.duration;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct Sample {
+    at: SystemTime,
+    /// Unit: mA
+    current: i32,
+    /// Unit: mV
+    voltage: u32,
+}
+
+impl Sample {
+    /// Unit: W
+    fn power(&self) -> f64 {
+        self.current as f64 * self.voltage as f64 / 1_000_000.0
+    }
+}
+
+/// Integrates power and current over timestamped readings
+///
+/// Samples may come at irregular intervals; each interval is integrated with the
+/// trapezoidal rule, charging and discharging separately. Intervals longer than
+/// the maximum gap are skipped, as nothing is known about them.
+///
+/// The accumulator is serializable, so its totals can be persisted across restarts.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnergyAccumulator {
+    max_gap: Duration,
+    session: EnergyTotals,
+    lifetime: EnergyTotals,
+    last: Option<Sample>,
+}
+
+impl Default for EnergyAccumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EnergyAccumulator {
+    pub fn new() -> Self {
+        Self {
+            max_gap: Duration::from_secs(60),
+            session: EnergyTotals::default(),
+            lifetime: EnergyTotals::default(),
+            last: None,
+        }
+    }
+
+    /// Sets the longest interval between samples which is integrated (default: 60 s)
+    pub fn with_max_gap(mut self, max_gap: Duration) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Feeds `current` and `bm_voltage` of `state` read at `at`
+    /// Returns the instantaneous power, positive while charging.
+    /// Unit: W
+    pub fn update(&mut self, state: &(impl BatteryState + ?Sized), at: SystemTime) -> Result<f64> {
+        let current = state.current()?;
+        let voltage = state.bm_voltage()?;
+        Ok(self.update_sample(current, voltage, at))
+    }
+
+    /// Feeds a current (mA, positive while charging) and a voltage (mV) read at `at`
+    /// Returns the instantaneous power, positive while charging.
+    /// Unit: W
+    pub fn update_sample(&mut self, current: i32, voltage: u32, at: SystemTime) -> f64 {
+        let sample = Sample {
+            at,
+            current,
+            voltage,
+        };
+        if let Some(last) = self.last {
+            match at.duration_since(last.at) {
+                Ok(interval) if interval <= self.max_gap => {
+                    let totals = integrate(&last, &sample, interval);
+                    self.session.add(&totals);
+                    self.lifetime.add(&totals);
+                }
+                // out of order or after a gap: start over from this sample
+                _ => {}
+            }
+        }
+        self.last = Some(sample);
+        sample.power()
+    }
+
+    /// Returns the latest instantaneous power, positive while charging
+    /// Unit: W
+    pub fn power(&self) -> Option<f64> {
+        self.last.map(|sample| sample.power())
+    }
+
+    /// Returns the totals since the session started
+    pub fn session(&self) -> &EnergyTotals {
+        &self.session
+    }
+
+    /// Returns the totals since the accumulator was created
+    pub fn lifetime(&self) -> &EnergyTotals {
+        &self.lifetime
+    }
+
+    /// Starts a new session, e.g. a mission, and returns the totals of the previous one
+    pub fn start_session(&mut self) -> EnergyTotals {
+        std::mem::take(&mut self.session)
+    }
+}
+
+fn integrate(from: &Sample, to: &Sample, interval: Duration) -> EnergyTotals {
+    let hours = interval.as_secs_f64() / 3600.0;
+    // (charged, discharged), split where the value crosses zero
+    let trapezoid = |a: f64, b: f64| {
+        if a * b < 0.0 {
+            let crossing = a / (a - b);
+            let first = a / 2.0 * crossing * hours;
+            let second = b / 2.0 * (1.0 - crossing) * hours;
+            if a > 0.0 {
+                (first, -second)
+            } else {
+                (second, -first)
+            }
+        } else if a + b > 0.0 {
+            ((a + b) / 2.0 * hours, 0.0)
+        } else {
+            (0.0, -(a + b) / 2.0 * hours)
+        }
+    };
+    let (charged_energy, discharged_energy) = trapezoid(from.power(), to.power());
+    let (charged_capacity, discharged_capacity) =
+        trapezoid(from.current as f64 / 1000.0, to.current as f64 / 1000.0);
+    EnergyTotals {
+        charged_energy,
+        discharged_energy,
+        charged_capacity,
+        discharged_capacity,
+        duration: interval,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_irregular_intervals() {
+        let mut accumulator = EnergyAccumulator::new();
+        let state = BatterySnapshot {
+            current: Some(-10000),
+            bm_voltage: Some(50000),
+            ..Default::default()
+        };
+        let mut secs = 0;
+        for step in [10, 5, 20, 25].iter().cycle().take(60) {
+            assert_close(accumulator.update(&state, at(secs)).unwrap(), -500.0);
+            secs += step;
+        }
+        accumulator.update(&state, at(secs)).unwrap();
+        // 900 s
+        let session = accumulator.session();
+        assert_eq!(session.duration, Duration::from_secs(900));
+        assert_close(session.discharged_energy, 125.0);
+        assert_close(session.discharged_capacity, 2.5);
+        assert_close(session.charged_energy, 0.0);
+        assert_close(session.net_energy(), -125.0);
+    }
+
+    #[test]
+    fn test_sign_change_and_gap() {
+        let mut accumulator = EnergyAccumulator::new();
+        accumulator.update_sample(-3600, 50000, at(0));
+        accumulator.update_sample(3600, 50000, at(60));
+        // crossing zero at 30 s
+        assert_close(accumulator.session().charged_capacity, 0.015);
+        assert_close(accumulator.session().discharged_capacity, 0.015);
+
+        // gap longer than 60 s is skipped
+        accumulator.update_sample(3600, 50000, at(1000));
+        assert_eq!(accumulator.session().duration, Duration::from_secs(60));
+        accumulator.update_sample(3600, 50000, at(1060));
+        assert_close(accumulator.session().charged_capacity, 0.075);
+    }
+
+    #[test]
+    fn test_sessions_and_persistence() {
+        let mut accumulator = EnergyAccumulator::new();
+        accumulator.update_sample(-36000, 50000, at(0));
+        accumulator.update_sample(-36000, 50000, at(10));
+        let previous = accumulator.start_session();
+        assert_close(previous.discharged_energy, 5.0);
+        assert_eq!(accumulator.session(), &EnergyTotals::default());
+
+        accumulator.update_sample(-36000, 50000, at(20));
+        assert_close(accumulator.session().discharged_energy, 5.0);
+        assert_close(accumulator.lifetime().discharged_energy, 10.0);
+
+        let json = serde_json::to_string(&accumulator).unwrap();
+        let restored: EnergyAccumulator = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, accumulator);
+    }
+}
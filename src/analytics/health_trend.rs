@@ -0,0 +1,365 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::regression::linear_regression;
+use crate::{battery_state::BatteryState, error::Result};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Serialized in snake case, e.g. `"full_charge_capacity"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthMetric {
+    /// Unit: %
+    StateOfHealth,
+    /// Unit: mAh
+    FullChargeCapacity,
+}
+
+/// How a health metric fades over time
+/// Serialized in snake case, e.g. `"square_root"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeModel {
+    /// `value = intercept + slope * days`
+    Linear,
+    /// `value = intercept + slope * sqrt(days)`, typical of calendar aging
+    SquareRoot,
+}
+
+impl FadeModel {
+    fn transform(&self, days: f64) -> f64 {
+        match self {
+            FadeModel::Linear => days,
+            FadeModel::SquareRoot => days.max(0.0).sqrt(),
+        }
+    }
+
+    fn inverse(&self, x: f64) -> f64 {
+        match self {
+            FadeModel::Linear => x,
+            FadeModel::SquareRoot => x * x,
+        }
+    }
+}
+
+/// Daily mean of the health metrics
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DailyHealth {
+    /// Days since the Unix epoch
+    pub day: u64,
+    /// Unit: %
+    pub state_of_health: Option<f64>,
+    /// Unit: mAh
+    pub full_charge_capacity: Option<f64>,
+}
+
+impl DailyHealth {
+    fn value(&self, metric: HealthMetric) -> Option<f64> {
+        match metric {
+            HealthMetric::StateOfHealth => self.state_of_health,
+            HealthMetric::FullChargeCapacity => self.full_charge_capacity,
+        }
+    }
+}
+
+/// A fade model fitted to daily points
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FadeFit {
+    pub metric: HealthMetric,
+    pub model: FadeModel,
+    pub slope: f64,
+    pub intercept: f64,
+    /// Coefficient of determination, 1.0 for a perfect fit
+    pub r_squared: f64,
+    /// Day the model's time is counted from
+    pub origin_day: u64,
+}
+
+impl FadeFit {
+    /// Returns the value the model predicts at `at`
+    pub fn predict(&self, at: SystemTime) -> f64 {
+        let days = days_since_epoch(at) - self.origin_day as f64;
+        self.intercept + self.slope * self.model.transform(days)
+    }
+
+    /// Returns when the model reaches `threshold`
+    /// `None` if it never fades to it or only too far ahead to be represented.
+    pub fn crossing(&self, threshold: f64) -> Option<SystemTime> {
+        if self.slope >= 0.0 {
+            return None;
+        }
+        let x = (threshold - self.intercept) / self.slope;
+        if x < 0.0 {
+            return day_to_time(self.origin_day as f64);
+        }
+        day_to_time(self.origin_day as f64 + self.model.inverse(x))
+    }
+}
+
+fn days_since_epoch(at: SystemTime) -> f64 {
+    at.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+        / SECONDS_PER_DAY as f64
+}
+
+fn day_to_time(day: f64) -> Option<SystemTime> {
+    let since_epoch = Duration::try_from_secs_f64(day.max(0.0) * SECONDS_PER_DAY as f64).ok()?;
+    SystemTime::UNIX_EPOCH.checked_add(since_epoch)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct DayAccumulator {
+    day: u64,
+    state_of_health: (f64, u32),
+    full_charge_capacity: (f64, u32),
+}
+
+impl DayAccumulator {
+    fn to_daily(&self) -> DailyHealth {
+        let mean = |(sum, count): (f64, u32)| (count > 0).then(|| sum / count as f64);
+        DailyHealth {
+            day: self.day,
+            state_of_health: mean(self.state_of_health),
+            full_charge_capacity: mean(self.full_charge_capacity),
+        }
+    }
+}
+
+/// Tracks state of health and full charge capacity over the long term
+///
+/// Samples are averaged per day (UTC). Fade models are fitted to the daily points
+/// to forecast when the module reaches its end of life.
+///
+/// The tracker is serializable, so its history can be persisted across restarts.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HealthTrendTracker {
+    end_of_life: f64,
+    days: Vec<DailyHealth>,
+    today: Option<DayAccumulator>,
+}
+
+impl Default for HealthTrendTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthTrendTracker {
+    pub fn new() -> Self {
+        Self {
+            end_of_life: 80.0,
+            days: vec![],
+            today: None,
+        }
+    }
+
+    /// Sets the state of health at which the module reaches its end of life (default: 80)
+    /// Unit: %
+    pub fn with_end_of_life(mut self, state_of_health: f64) -> Self {
+        self.end_of_life = state_of_health;
+        self
+    }
+
+    /// Feeds `state_of_health` and `full_charge_capacity` of `state` read at `at`
+    /// Fails only if neither of them can be read.
+    pub fn update(&mut self, state: &(impl BatteryState + ?Sized), at: SystemTime) -> Result<()> {
+        let state_of_health = state.state_of_health();
+        let full_charge_capacity = state.full_charge_capacity();
+        if state_of_health.is_err() && full_charge_capacity.is_err() {
+            return state_of_health.map(|_| ());
+        }
+        self.update_sample(
+            state_of_health.ok().map(|value| value as f64),
+            full_charge_capacity.ok().map(|value| value as f64),
+            at,
+        );
+        Ok(())
+    }
+
+    /// Feeds a state of health (%) and a full charge capacity (mAh) read at `at`
+    /// Samples from before the latest day are ignored.
+    pub fn update_sample(
+        &mut self,
+        state_of_health: Option<f64>,
+        full_charge_capacity: Option<f64>,
+        at: SystemTime,
+    ) {
+        let day = days_since_epoch(at) as u64;
+        match &self.today {
+            Some(today) if today.day == day => {}
+            Some(today) if today.day > day => return,
+            _ => {
+                if let Some(today) = self.today.take() {
+                    self.days.push(today.to_daily());
+                }
+                self.today = Some(DayAccumulator {
+                    day,
+                    ..Default::default()
+                });
+            }
+        }
+        let today = self.today.as_mut().unwrap();
+        if let Some(value) = state_of_health {
+            today.state_of_health.0 += value;
+            today.state_of_health.1 += 1;
+        }
+        if let Some(value) = full_charge_capacity {
+            today.full_charge_capacity.0 += value;
+            today.full_charge_capacity.1 += 1;
+        }
+    }
+
+    /// Returns the daily points, including today's so far
+    pub fn days(&self) -> Vec<DailyHealth> {
+        let mut days = self.days.clone();
+        days.extend(self.today.as_ref().map(|today| today.to_daily()));
+        days
+    }
+
+    /// Fits `model` to the daily points of `metric`
+    /// Returns `None` with fewer than two points.
+    pub fn fit(&self, metric: HealthMetric, model: FadeModel) -> Option<FadeFit> {
+        let days = self.days();
+        let points: Vec<_> = days
+            .iter()
+            .filter_map(|daily| Some((daily.day, daily.value(metric)?)))
+            .collect();
+        let origin_day = points.first()?.0;
+        let transformed: Vec<_> = points
+            .iter()
+            .map(|(day, value)| (model.transform((day - origin_day) as f64), *value))
+            .collect();
+        let (slope, intercept) = linear_regression(transformed.iter().copied())?;
+
+        let mean = transformed.iter().map(|(_, y)| y).sum::<f64>() / transformed.len() as f64;
+        let total: f64 = transformed.iter().map(|(_, y)| (y - mean).powi(2)).sum();
+        let residual: f64 = transformed
+            .iter()
+            .map(|(x, y)| (y - (intercept + slope * x)).powi(2))
+            .sum();
+        let r_squared = if total > 0.0 {
+            1.0 - residual / total
+        } else {
+            1.0
+        };
+        Some(FadeFit {
+            metric,
+            model,
+            slope,
+            intercept,
+            r_squared,
+            origin_day,
+        })
+    }
+
+    /// Returns the fit of the model which explains the state of health best
+    pub fn best_fit(&self) -> Option<FadeFit> {
+        [FadeModel::Linear, FadeModel::SquareRoot]
+            .into_iter()
+            .filter_map(|model| self.fit(HealthMetric::StateOfHealth, model))
+            .max_by(|a, b| a.r_squared.total_cmp(&b.r_squared))
+    }
+
+    /// Forecasts when the state of health reaches the end of life, using the best fitting model
+    pub fn forecast_end_of_life(&self) -> Option<SystemTime> {
+        self.best_fit()?.crossing(self.end_of_life)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn day(day: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(day * SECONDS_PER_DAY)
+    }
+
+    #[test]
+    fn test_daily_points() {
+        let mut tracker = HealthTrendTracker::new();
+        tracker.update_sample(Some(99.0), Some(40000.0), day(10));
+        tracker.update_sample(Some(98.0), None, day(10) + Duration::from_secs(3600));
+        tracker.update_sample(Some(97.0), Some(39000.0), day(11));
+        assert_eq!(
+            tracker.days(),
+            vec![
+                DailyHealth {
+                    day: 10,
+                    state_of_health: Some(98.5),
+                    full_charge_capacity: Some(40000.0)
+                },
+                DailyHealth {
+                    day: 11,
+                    state_of_health: Some(97.0),
+                    full_charge_capacity: Some(39000.0)
+                },
+            ]
+        );
+        assert!(tracker
+            .update(&BatterySnapshot::default(), day(12))
+            .is_err());
+    }
+
+    #[test]
+    fn test_linear_forecast() {
+        let mut tracker = HealthTrendTracker::new();
+        // 1 % per 30 days
+        for d in 0..=90 {
+            let state = BatterySnapshot {
+                state_of_health: Some(100 - d / 30),
+                full_charge_capacity: Some(40000 - 10 * d),
+                ..Default::default()
+            };
+            tracker.update(&state, day(1000 + d as u64)).unwrap();
+        }
+        let fit = tracker
+            .fit(HealthMetric::FullChargeCapacity, FadeModel::Linear)
+            .unwrap();
+        assert!((fit.slope + 10.0).abs() < 1e-6);
+        assert!((fit.r_squared - 1.0).abs() < 1e-9);
+        assert!((fit.predict(day(1100)) - 39000.0).abs() < 1e-6);
+
+        let forecast = tracker.forecast_end_of_life().unwrap();
+        let days = days_since_epoch(forecast) - 1000.0;
+        // around 600 days to lose 20 %
+        assert!((500.0..700.0).contains(&days), "{days}");
+    }
+
+    #[test]
+    fn test_square_root_fit() {
+        let mut tracker = HealthTrendTracker::new().with_end_of_life(90.0);
+        for d in 0..=100u64 {
+            let state_of_health = 100.0 - (d as f64).sqrt();
+            tracker.update_sample(Some(state_of_health), None, day(d));
+        }
+        let best = tracker.best_fit().unwrap();
+        assert_eq!(best.model, FadeModel::SquareRoot);
+        assert!((best.slope + 1.0).abs() < 1e-9);
+        let forecast = tracker.forecast_end_of_life().unwrap();
+        assert!((days_since_epoch(forecast) - 100.0).abs() < 1e-6);
+
+        let serialized = serde_json::to_string(&tracker).unwrap();
+        let restored: HealthTrendTracker = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(restored.days().len(), 101);
+        assert_eq!(restored.best_fit().unwrap().model, FadeModel::SquareRoot);
+    }
+
+    #[test]
+    fn test_crossing_out_of_range() {
+        let fit = FadeFit {
+            metric: HealthMetric::StateOfHealth,
+            model: FadeModel::SquareRoot,
+            slope: -1e-9,
+            intercept: 100.0,
+            r_squared: 1.0,
+            origin_day: 0,
+        };
+        assert_eq!(fit.crossing(70.0), None);
+        // already below the threshold
+        assert_eq!(fit.crossing(100.5), Some(SystemTime::UNIX_EPOCH));
+    }
+}
@@ -0,0 +1,37 @@
+/// Fits `y = slope * x + intercept` by least squares
+/// Returns `(slope, intercept)`, or `None` with fewer than two distinct `x`.
+pub(super) fn linear_regression(
+    points: impl IntoIterator<Item = (f64, f64)>,
+) -> Option<(f64, f64)> {
+    let (mut n, mut sum_x, mut sum_y, mut sum_xx, mut sum_xy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (x, y) in points {
+        n += 1.0;
+        sum_x += x;
+        sum_y += y;
+        sum_xx += x * x;
+        sum_xy += x * y;
+    }
+    let denominator = n * sum_xx - sum_x * sum_x;
+    if n < 2.0 || denominator.abs() < f64::EPSILON * n * sum_xx.max(1.0) {
+        return None;
+    }
+    let slope = (n * sum_xy - sum_x * sum_y) / denominator;
+    let intercept = (sum_y - slope * sum_x) / n;
+    Some((slope, intercept))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_regression() {
+        let (slope, intercept) =
+            linear_regression([(0.0, 1.0), (1.0, 3.0), (2.0, 5.0), (3.0, 7.0)]).unwrap();
+        assert!((slope - 2.0).abs() < 1e-9);
+        assert!((intercept - 1.0).abs() < 1e-9);
+
+        assert_eq!(linear_regression([(1.0, 1.0)]), None);
+        assert_eq!(linear_regression([(1.0, 1.0), (1.0, 2.0)]), None);
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::regression::linear_regression;
+use crate::{battery_state::BatteryState, error::Result};
+
+/// DC internal resistance computed from one current step
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResistanceEstimate {
+    /// When the step ended
+    pub at: SystemTime,
+    /// Change of the current, positive when it rose
+    /// Unit: mA
+    pub current_step: i32,
+    /// Unit: mOhm
+    pub pack: Option<f64>,
+    /// Per cell, `None` for a cell whose voltage did not move plausibly
+    /// Unit: mOhm
+    pub cells: Vec<Option<f64>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Sample {
+    at: SystemTime,
+    /// Unit: mA
+    current: i32,
+    /// Unit: mV
+    bm_voltage: Option<u32>,
+    /// Unit: mV
+    cell_voltages: Vec<u32>,
+}
+
+/// Estimates DC internal resistance from sharp changes of the current
+///
+/// Two consecutive samples close enough in time and whose currents differ by at least
+/// the minimum step qualify as a step event. The resistance is the voltage change
+/// divided by the current change, for the module and for each cell.
+///
+/// The latest estimates are kept to track the resistance over time; rising
+/// resistance is an early sign of a degrading cell.
+#[derive(Clone, Debug)]
+pub struct ResistanceEstimator {
+    min_current_step: u32,
+    max_interval: Duration,
+    window: usize,
+    last: Option<Sample>,
+    estimates: VecDeque<ResistanceEstimate>,
+}
+
+impl Default for ResistanceEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResistanceEstimator {
+    pub fn new() -> Self {
+        Self {
+            min_current_step: 5000,
+            max_interval: Duration::from_secs(2),
+            window: 100,
+            last: None,
+            estimates: VecDeque::new(),
+        }
+    }
+
+    /// Sets the smallest current change which qualifies as a step (default: 5000)
+    /// Unit: mA
+    pub fn with_min_current_step(mut self, min_current_step: u32) -> Self {
+        self.min_current_step = min_current_step;
+        self
+    }
+
+    /// Sets the longest interval between the samples of a step (default: 2 s)
+    /// Longer intervals mix in polarization and changes of the state of charge.
+    pub fn with_max_interval(mut self, max_interval: Duration) -> Self {
+        self.max_interval = max_interval;
+        self
+    }
+
+    /// Sets the number of latest estimates kept (default: 100)
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Feeds `current`, and if available `bm_voltage` and `cell_voltages`, of `state` read at `at`
+    /// Returns the estimate if a step event was detected.
+    pub fn update(
+        &mut self,
+        state: &(impl BatteryState + ?Sized),
+        at: SystemTime,
+    ) -> Result<Option<ResistanceEstimate>> {
+        let current = state.current()?;
+        let bm_voltage = state.bm_voltage().ok();
+        let cell_voltages = state.cell_voltages().unwrap_or_default();
+        Ok(self.update_sample(current, bm_voltage, &cell_voltages, at))
+    }
+
+    /// Feeds a current (mA, positive while charging) and voltages (mV) read at `at`
+    /// Returns the estimate if a step event was detected.
+    pub fn update_sample(
+        &mut self,
+        current: i32,
+        bm_voltage: Option<u32>,
+        cell_voltages: &[u32],
+        at: SystemTime,
+    ) -> Option<ResistanceEstimate> {
+        let sample = Sample {
+            at,
+            current,
+            bm_voltage,
+            cell_voltages: cell_voltages.to_vec(),
+        };
+        let last = self.last.replace(sample.clone())?;
+        let interval = at.duration_since(last.at).ok()?;
+        let current_step = sample.current - last.current;
+        if interval > self.max_interval || current_step.unsigned_abs() < self.min_current_step {
+            return None;
+        }
+
+        // positive current charges, so the voltage moves the same way as the current
+        let resistance = |from: u32, to: u32| {
+            let resistance = (to as f64 - from as f64) / current_step as f64 * 1000.0;
+            (resistance > 0.0).then_some(resistance)
+        };
+        let pack = last
+            .bm_voltage
+            .zip(sample.bm_voltage)
+            .and_then(|(from, to)| resistance(from, to));
+        let cells = if last.cell_voltages.len() == sample.cell_voltages.len() {
+            last.cell_voltages
+                .iter()
+                .zip(&sample.cell_voltages)
+                .map(|(from, to)| resistance(*from, *to))
+                .collect()
+        } else {
+            vec![]
+        };
+        if pack.is_none() && cells.iter().all(Option::is_none) {
+            return None;
+        }
+
+        let estimate = ResistanceEstimate {
+            at,
+            current_step,
+            pack,
+            cells,
+        };
+        self.estimates.push_back(estimate.clone());
+        while self.estimates.len() > self.window {
+            self.estimates.pop_front();
+        }
+        Some(estimate)
+    }
+
+    /// Returns the latest estimates, oldest first
+    pub fn estimates(&self) -> impl Iterator<Item = &ResistanceEstimate> {
+        self.estimates.iter()
+    }
+
+    pub fn latest(&self) -> Option<&ResistanceEstimate> {
+        self.estimates.back()
+    }
+
+    /// Returns the mean module resistance over the latest estimates
+    /// Unit: mOhm
+    pub fn pack_resistance(&self) -> Option<f64> {
+        mean(self.estimates.iter().filter_map(|estimate| estimate.pack))
+    }
+
+    /// Returns the mean resistance of each cell over the latest estimates
+    /// Unit: mOhm
+    pub fn cell_resistances(&self) -> Vec<Option<f64>> {
+        let cells = self
+            .estimates
+            .iter()
+            .map(|estimate| estimate.cells.len())
+            .max()
+            .unwrap_or(0);
+        (0..cells)
+            .map(|cell| {
+                mean(
+                    self.estimates
+                        .iter()
+                        .filter_map(|estimate| estimate.cells.get(cell).copied().flatten()),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the cell with the highest mean resistance
+    pub fn highest_resistance_cell(&self) -> Option<usize> {
+        self.cell_resistances()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(cell, resistance)| Some((cell, resistance?)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(cell, _)| cell)
+    }
+
+    /// Returns how fast the module resistance changes over the latest estimates
+    /// Unit: mOhm/day
+    pub fn pack_resistance_trend(&self) -> Option<f64> {
+        let start = self.estimates.front()?.at;
+        linear_regression(self.estimates.iter().filter_map(|estimate| {
+            let days = estimate
+                .at
+                .duration_since(start)
+                .unwrap_or_default()
+                .as_secs_f64()
+                / 86400.0;
+            Some((days, estimate.pack?))
+        }))
+        .map(|(slope, _)| slope)
+    }
+
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.estimates.clear();
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn at(millis: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_step_event() {
+        let mut estimator = ResistanceEstimator::new();
+        let idle = BatterySnapshot {
+            current: Some(-1000),
+            bm_voltage: Some(52000),
+            cell_voltages: Some(vec![3250, 3250, 3250, 3250]),
+            ..Default::default()
+        };
+        assert_eq!(estimator.update(&idle, at(0)).unwrap(), None);
+        // motors start: 20 A more discharge
+        let load = BatterySnapshot {
+            current: Some(-21000),
+            bm_voltage: Some(51600),
+            cell_voltages: Some(vec![3230, 3230, 3210, 3230]),
+            ..Default::default()
+        };
+        let estimate = estimator.update(&load, at(500)).unwrap().unwrap();
+        assert_eq!(estimate.current_step, -20000);
+        assert_close(estimate.pack.unwrap(), 20.0);
+        assert_close(estimate.cells[0].unwrap(), 1.0);
+        assert_close(estimate.cells[2].unwrap(), 2.0);
+        assert_eq!(estimator.highest_resistance_cell(), Some(2));
+
+        // small changes do not qualify
+        let steady = BatterySnapshot {
+            current: Some(-22000),
+            ..load.clone()
+        };
+        assert_eq!(estimator.update(&steady, at(1000)).unwrap(), None);
+        assert!(estimator
+            .update(&BatterySnapshot::default(), at(1500))
+            .is_err());
+    }
+
+    #[test]
+    fn test_slow_step_ignored() {
+        let mut estimator = ResistanceEstimator::new();
+        estimator.update_sample(0, Some(52000), &[], at(0));
+        assert_eq!(
+            estimator.update_sample(-20000, Some(51600), &[], at(5000)),
+            None
+        );
+        // implausible direction is dropped
+        assert_eq!(estimator.update_sample(0, Some(51500), &[], at(5500)), None);
+    }
+
+    #[test]
+    fn test_trend() {
+        let mut estimator = ResistanceEstimator::new();
+        let day = 86_400_000;
+        for d in 0..10 {
+            // 0.5 mOhm more each day
+            let drop = 400 + 10 * d as u32;
+            estimator.update_sample(0, Some(52000), &[], at(d * day));
+            estimator.update_sample(-20000, Some(52000 - drop), &[], at(d * day + 100));
+        }
+        assert_eq!(estimator.estimates().count(), 10);
+        assert_close(estimator.pack_resistance().unwrap(), 22.25);
+        assert!((estimator.pack_resistance_trend().unwrap() - 0.5).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,497 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{battery_state::BatteryState, error::Result};
+
+// z for a 95 % confidence interval
+const Z: f64 = 1.96;
+
+/// How `RuntimeEstimator` smooths the current
+/// Serialized in snake case, e.g. `{"ema": {"time_constant": ...}}`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Smoothing {
+    /// Exponential moving average
+    Ema { time_constant: Duration },
+    /// Mean over the latest samples
+    Window { duration: Duration },
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Smoothing::Ema {
+            time_constant: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Expected discharge, as segments of constant current repeated until the module is empty
+/// Current is positive while charging, so discharging segments are negative.
+/// Unit: mA
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LoadProfile(pub Vec<(Duration, i32)>);
+
+impl LoadProfile {
+    /// Returns how long `capacity` (mAh) lasts with the currents scaled by `scale`
+    fn time_to_consume(&self, capacity: f64, scale: f64) -> Option<Duration> {
+        let total: f64 = self
+            .0
+            .iter()
+            .map(|(duration, current)| (-*current).max(0) as f64 * scale * duration.as_secs_f64())
+            .sum();
+        if total <= 0.0 {
+            return None;
+        }
+        // whole repetitions first, then segment by segment
+        // an exact multiple of a period ends within the last repetition, not after it
+        let mut remaining = capacity * 3600.0;
+        let repetitions = ((remaining / total).ceil() - 1.0).max(0.0);
+        remaining -= repetitions * total;
+        let period: f64 = self
+            .0
+            .iter()
+            .map(|(duration, _)| duration.as_secs_f64())
+            .sum();
+        let mut seconds = repetitions * period;
+        for (duration, current) in &self.0 {
+            if remaining <= 0.0 {
+                break;
+            }
+            let rate = (-*current).max(0) as f64 * scale;
+            if rate == 0.0 {
+                seconds += duration.as_secs_f64();
+                continue;
+            }
+            let consumed = rate * duration.as_secs_f64();
+            if consumed >= remaining {
+                seconds += remaining / rate;
+                return Some(seconds_to_duration(seconds));
+            }
+            remaining -= consumed;
+            seconds += duration.as_secs_f64();
+        }
+        Some(seconds_to_duration(seconds))
+    }
+}
+
+/// Serialized in snake case, e.g. `"time_to_empty"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeKind {
+    TimeToEmpty,
+    TimeToFull,
+}
+
+/// Estimated time to empty or to full, with a 95 % confidence interval
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeEstimate {
+    pub kind: RuntimeKind,
+    pub expected: Duration,
+    pub lower: Duration,
+    pub upper: Duration,
+}
+
+#[derive(Clone, Debug)]
+enum Smoother {
+    Ema {
+        time_constant: Duration,
+        last: Option<SystemTime>,
+        mean: f64,
+        variance: f64,
+    },
+    Window {
+        duration: Duration,
+        samples: VecDeque<(SystemTime, f64)>,
+    },
+}
+
+impl Smoother {
+    fn new(smoothing: Smoothing) -> Self {
+        match smoothing {
+            Smoothing::Ema { time_constant } => Smoother::Ema {
+                time_constant,
+                last: None,
+                mean: 0.0,
+                variance: 0.0,
+            },
+            Smoothing::Window { duration } => Smoother::Window {
+                duration,
+                samples: VecDeque::new(),
+            },
+        }
+    }
+
+    fn update(&mut self, value: f64, at: SystemTime) {
+        match self {
+            Smoother::Ema {
+                time_constant,
+                last,
+                mean,
+                variance,
+            } => {
+                match last.and_then(|last| at.duration_since(last).ok()) {
+                    Some(dt) => {
+                        let alpha = if time_constant.is_zero() {
+                            1.0
+                        } else {
+                            1.0 - (-dt.as_secs_f64() / time_constant.as_secs_f64()).exp()
+                        };
+                        let difference = value - *mean;
+                        *mean += alpha * difference;
+                        *variance = (1.0 - alpha) * (*variance + alpha * difference * difference);
+                    }
+                    None => {
+                        *mean = value;
+                        *variance = 0.0;
+                    }
+                }
+                *last = Some(at);
+            }
+            Smoother::Window { duration, samples } => {
+                samples.push_back((at, value));
+                while let Some((first, _)) = samples.front() {
+                    match at.duration_since(*first) {
+                        Ok(age) if age > *duration => {
+                            samples.pop_front();
+                        }
+                        _ => break,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns (mean, standard deviation)
+    fn get(&self) -> Option<(f64, f64)> {
+        match self {
+            Smoother::Ema {
+                last,
+                mean,
+                variance,
+                ..
+            } => last.map(|_| (*mean, variance.sqrt())),
+            Smoother::Window { samples, .. } => {
+                if samples.is_empty() {
+                    return None;
+                }
+                let n = samples.len() as f64;
+                let mean = samples.iter().map(|(_, v)| v).sum::<f64>() / n;
+                let variance = samples.iter().map(|(_, v)| (v - mean).powi(2)).sum::<f64>() / n;
+                Some((mean, variance.sqrt()))
+            }
+        }
+    }
+}
+
+/// Estimates time to empty while discharging and time to full while charging
+///
+/// Time to empty divides the remaining capacity by the smoothed current, or plays the load profile if set.
+/// Time to full assumes the charging current tapers linearly with the charged capacity, from the
+/// current at the start of the constant voltage phase to the termination current.
+/// The confidence interval reflects how much the current varies.
+#[derive(Clone, Debug)]
+pub struct RuntimeEstimator {
+    smoother: Smoother,
+    load_profile: Option<LoadProfile>,
+    constant_voltage_threshold: f64,
+    termination_current: Option<u32>,
+    idle_current: u32,
+    /// (remaining capacity, full charge capacity) in mAh
+    capacity: Option<(u32, u32)>,
+    last_current: i32,
+}
+
+impl Default for RuntimeEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuntimeEstimator {
+    pub fn new() -> Self {
+        Self {
+            smoother: Smoother::new(Smoothing::default()),
+            load_profile: None,
+            constant_voltage_threshold: 90.0,
+            termination_current: None,
+            idle_current: 100,
+            capacity: None,
+            last_current: 0,
+        }
+    }
+
+    /// Sets how the current is smoothed (default: EMA with a time constant of 60 s)
+    pub fn with_smoothing(mut self, smoothing: Smoothing) -> Self {
+        self.smoother = Smoother::new(smoothing);
+        self
+    }
+
+    /// Uses `load_profile` instead of the measured current for time to empty
+    pub fn with_load_profile(mut self, load_profile: LoadProfile) -> Self {
+        self.load_profile = Some(load_profile);
+        self
+    }
+
+    /// Sets the state of charge where the constant voltage phase starts (default: 90)
+    /// Unit: %
+    pub fn with_constant_voltage_threshold(mut self, threshold: f64) -> Self {
+        self.constant_voltage_threshold = threshold;
+        self
+    }
+
+    /// Sets the current where charging ends (default: full charge capacity / 20)
+    /// Unit: mA
+    pub fn with_termination_current(mut self, current: u32) -> Self {
+        self.termination_current = Some(current);
+        self
+    }
+
+    /// Sets the current below which the module is idle and nothing is estimated (default: 100)
+    /// Unit: mA
+    pub fn with_idle_current(mut self, current: u32) -> Self {
+        self.idle_current = current;
+        self
+    }
+
+    /// Feeds `current`, `remaining_capacity` and `full_charge_capacity` of `state` read at `at`
+    pub fn update(&mut self, state: &(impl BatteryState + ?Sized), at: SystemTime) -> Result<()> {
+        let current = state.current()?;
+        let remaining_capacity = state.remaining_capacity()?;
+        let full_charge_capacity = state.full_charge_capacity()?;
+        self.update_sample(current, remaining_capacity, full_charge_capacity, at);
+        Ok(())
+    }
+
+    /// Feeds a current (mA, positive while charging) and capacities (mAh) read at `at`
+    pub fn update_sample(
+        &mut self,
+        current: i32,
+        remaining_capacity: u32,
+        full_charge_capacity: u32,
+        at: SystemTime,
+    ) {
+        // the smoothed current is meaningless across a change between charging and discharging
+        if (current > 0) != (self.last_current > 0) && current != 0 {
+            self.smoother = Smoother::new(self.smoothing());
+        }
+        self.smoother.update(current as f64, at);
+        self.capacity = Some((remaining_capacity, full_charge_capacity));
+        self.last_current = current;
+    }
+
+    fn smoothing(&self) -> Smoothing {
+        match self.smoother {
+            Smoother::Ema { time_constant, .. } => Smoothing::Ema { time_constant },
+            Smoother::Window { duration, .. } => Smoothing::Window { duration },
+        }
+    }
+
+    /// Returns the smoothed current, positive while charging
+    /// Unit: mA
+    pub fn smoothed_current(&self) -> Option<f64> {
+        self.smoother.get().map(|(mean, _)| mean)
+    }
+
+    /// Returns `None` while idle or before any sample
+    pub fn estimate(&self) -> Option<RuntimeEstimate> {
+        let (mean, deviation) = self.smoother.get()?;
+        let (remaining, full) = self.capacity?;
+        // at least 1 mA, so that nothing is divided by zero
+        let idle_current = self.idle_current.max(1) as f64;
+        if mean.abs() < idle_current {
+            return None;
+        }
+        let magnitude = mean.abs();
+        // currents giving the shortest and the longest time
+        let high = magnitude + Z * deviation;
+        let low = (magnitude - Z * deviation).max(idle_current);
+
+        if mean < 0.0 {
+            let remaining = remaining as f64;
+            let (expected, lower, upper) = match &self.load_profile {
+                Some(profile) => (
+                    profile.time_to_consume(remaining, 1.0)?,
+                    profile.time_to_consume(remaining, high / magnitude)?,
+                    profile.time_to_consume(remaining, low / magnitude)?,
+                ),
+                None => (
+                    hours(remaining / magnitude),
+                    hours(remaining / high),
+                    hours(remaining / low),
+                ),
+            };
+            Some(RuntimeEstimate {
+                kind: RuntimeKind::TimeToEmpty,
+                expected,
+                lower,
+                upper,
+            })
+        } else {
+            Some(RuntimeEstimate {
+                kind: RuntimeKind::TimeToFull,
+                expected: self.time_to_full(remaining, full, magnitude),
+                lower: self.time_to_full(remaining, full, high),
+                upper: self.time_to_full(remaining, full, low),
+            })
+        }
+    }
+
+    fn time_to_full(&self, remaining: u32, full: u32, current: f64) -> Duration {
+        let remaining = remaining as f64;
+        let full = full as f64;
+        let termination = self
+            .termination_current
+            .map(|current| current as f64)
+            .unwrap_or(full / 20.0)
+            .max(1.0);
+        let threshold = full * self.constant_voltage_threshold / 100.0;
+        // constant current phase, then the taper from `taper_current`
+        let (constant_current_hours, taper_capacity) = if remaining < threshold {
+            ((threshold - remaining) / current, full - threshold)
+        } else {
+            (0.0, (full - remaining).max(0.0))
+        };
+        let taper_hours = if current > termination {
+            taper_capacity / (current - termination) * (current / termination).ln()
+        } else {
+            taper_capacity / termination
+        };
+        hours(constant_current_hours + taper_hours)
+    }
+}
+
+fn hours(hours: f64) -> Duration {
+    seconds_to_duration(hours * 3600.0)
+}
+
+/// Saturates at `Duration::MAX`
+fn seconds_to_duration(seconds: f64) -> Duration {
+    Duration::try_from_secs_f64(seconds.max(0.0)).unwrap_or(Duration::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_time_to_empty() {
+        let mut estimator = RuntimeEstimator::new();
+        assert_eq!(estimator.estimate(), None);
+        for secs in 0..10 {
+            let state = BatterySnapshot {
+                current: Some(-10000),
+                remaining_capacity: Some(20000),
+                full_charge_capacity: Some(40000),
+                ..Default::default()
+            };
+            estimator.update(&state, at(secs)).unwrap();
+        }
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.kind, RuntimeKind::TimeToEmpty);
+        assert_eq!(estimate.expected, Duration::from_secs(2 * 3600));
+        // constant current, no uncertainty
+        assert_eq!(estimate.lower, estimate.expected);
+        assert_eq!(estimate.upper, estimate.expected);
+    }
+
+    #[test]
+    fn test_confidence_bounds() {
+        let mut estimator = RuntimeEstimator::new().with_smoothing(Smoothing::Window {
+            duration: Duration::from_secs(60),
+        });
+        for secs in 0..60 {
+            let current = if secs % 2 == 0 { -8000 } else { -12000 };
+            estimator.update_sample(current, 20000, 40000, at(secs));
+        }
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.expected, Duration::from_secs(2 * 3600));
+        assert!(estimate.lower < estimate.expected);
+        assert!(estimate.upper > estimate.expected);
+        assert_eq!(estimator.smoothed_current(), Some(-10000.0));
+    }
+
+    #[test]
+    fn test_load_profile() {
+        // 30 min at 20 A, then 30 min idle
+        let profile = LoadProfile(vec![
+            (Duration::from_secs(1800), -20000),
+            (Duration::from_secs(1800), 0),
+        ]);
+        let mut estimator = RuntimeEstimator::new().with_load_profile(profile);
+        estimator.update_sample(-10000, 25000, 40000, at(0));
+        let estimate = estimator.estimate().unwrap();
+        // 10 Ah per hour: 2 periods, then 5 Ah in 15 min
+        assert_eq!(estimate.expected, Duration::from_secs(2 * 3600 + 900));
+
+        // an exact multiple of a period runs out at the end of the last discharge
+        estimator.update_sample(-10000, 20000, 40000, at(1));
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.expected, Duration::from_secs(3600 + 1800));
+    }
+
+    #[test]
+    fn test_load_profile_starting_idle() {
+        let profile = LoadProfile(vec![
+            (Duration::from_secs(600), 0),
+            (Duration::from_secs(1800), -20000),
+        ]);
+        let mut estimator = RuntimeEstimator::new().with_load_profile(profile);
+        estimator.update_sample(-5000, 0, 10000, at(0));
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.expected, Duration::ZERO);
+        assert_eq!(estimate.upper, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_zero_idle_current() {
+        let mut estimator =
+            RuntimeEstimator::new()
+                .with_idle_current(0)
+                .with_smoothing(Smoothing::Window {
+                    duration: Duration::from_secs(60),
+                });
+        // the lower bound of the current is below zero
+        for secs in 0..60 {
+            let current = if secs % 2 == 0 { -10 } else { -1000 };
+            estimator.update_sample(current, 20000, 40000, at(secs));
+        }
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.upper, Duration::from_secs(20000 * 3600));
+
+        let mut estimator = RuntimeEstimator::new().with_idle_current(0);
+        estimator.update_sample(1, 20000, 40000, at(0));
+        assert!(estimator.estimate().is_some());
+        estimator.update_sample(0, 20000, 40000, at(3600));
+        assert_eq!(estimator.estimate(), None);
+    }
+
+    #[test]
+    fn test_time_to_full() {
+        let mut estimator = RuntimeEstimator::new()
+            .with_constant_voltage_threshold(90.0)
+            .with_termination_current(1000);
+        estimator.update_sample(10000, 20000, 40000, at(0));
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.kind, RuntimeKind::TimeToFull);
+        // 16 Ah at 10 A, then 4 Ah tapering from 10 A to 1 A
+        let taper = 4000.0 / 9000.0 * 10f64.ln();
+        let expected = (1.6 + taper) * 3600.0;
+        assert!((estimate.expected.as_secs_f64() - expected).abs() < 1e-3);
+        // slower than constant current all the way
+        assert!(estimate.expected > Duration::from_secs(2 * 3600));
+
+        // idle once the smoothed current has decayed
+        estimator.update_sample(0, 40000, 40000, at(3600));
+        assert_eq!(estimator.estimate(), None);
+    }
+}
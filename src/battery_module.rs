@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+use crate::{
+    battery_snapshot::BatterySnapshot,
+    error::{Error, Result},
+};
+
+/// Fields which can be read from a battery module
+/// Each field corresponds to a method of `BatteryState`.
+/// Serialized in snake case, e.g. `"cell_voltages"` or `"fail_status_1"`
+#[derive(Clone, Copy, Debug, Display, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryField {
+    CellVoltages,
+    Current,
+    Temperature,
+    RemainingCapacity,
+    FullChargeCapacity,
+    DesignCapacity,
+    AbsoluteStateOfCharge,
+    RelativeStateOfCharge,
+    StateOfHealth,
+    BmVoltage,
+    #[serde(rename = "fail_status_1")]
+    FailStatus1,
+    #[serde(rename = "fail_status_2")]
+    FailStatus2,
+    #[serde(rename = "fail_status_3")]
+    FailStatus3,
+}
+
+/// Backend-agnostic access to a battery module
+///
+/// Implementors fetch the requested fields through whatever they wrap
+/// (UART, CAN, a simulator, a replay, ...) and return them as a `BatterySnapshot`,
+/// which implements `BatteryState`.
+pub trait BatteryModule {
+    /// Returns the fields this module is able to read
+    fn supported_fields(&self) -> Vec<BatteryField>;
+
+    /// Reads the given fields
+    /// Returns `Error::UnsupportedField` if any of them is not supported.
+    fn read(&mut self, fields: &[BatteryField]) -> Result<BatterySnapshot>;
+
+    fn is_supported(&self, field: BatteryField) -> bool {
+        self.supported_fields().contains(&field)
+    }
+
+    /// Reads all the supported fields
+    fn read_all(&mut self) -> Result<BatterySnapshot> {
+        let fields = self.supported_fields();
+        self.read(&fields)
+    }
+}
+
+impl<M: BatteryModule + ?Sized> BatteryModule for Box<M> {
+    fn supported_fields(&self) -> Vec<BatteryField> {
+        (**self).supported_fields()
+    }
+
+    fn read(&mut self, fields: &[BatteryField]) -> Result<BatterySnapshot> {
+        (**self).read(fields)
+    }
+}
+
+/// A snapshot replays the values it holds.
+impl BatteryModule for BatterySnapshot {
+    fn supported_fields(&self) -> Vec<BatteryField> {
+        self.fields()
+    }
+
+    fn read(&mut self, fields: &[BatteryField]) -> Result<BatterySnapshot> {
+        let mut snapshot = BatterySnapshot::default();
+        for &field in fields {
+            snapshot
+                .capture(self, field)
+                .map_err(|_| Error::UnsupportedField(field))?;
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BatteryState;
+
+    #[test]
+    fn test_snapshot_as_battery_module() {
+        let mut module = BatterySnapshot {
+            current: Some(-1200),
+            relative_state_of_charge: Some(80),
+            ..Default::default()
+        };
+        assert_eq!(
+            module.supported_fields(),
+            vec![BatteryField::Current, BatteryField::RelativeStateOfCharge]
+        );
+        assert!(module.is_supported(BatteryField::Current));
+        assert!(!module.is_supported(BatteryField::Temperature));
+
+        let snapshot = module.read(&[BatteryField::Current]).unwrap();
+        assert_eq!(snapshot.current().unwrap(), -1200);
+        assert!(snapshot.relative_state_of_charge().is_err());
+
+        assert_eq!(module.read_all().unwrap(), module);
+
+        let result = module.read(&[BatteryField::Temperature]);
+        assert!(matches!(
+            result,
+            Err(Error::UnsupportedField(BatteryField::Temperature))
+        ));
+    }
+}
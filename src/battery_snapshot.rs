@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battery_module::BatteryField,
+    battery_state::BatteryState,
+    error::{Error, Result},
+    fail_status::*,
+};
+
+/// Decoded values of a battery module, independent of where they were read from
+/// Fields which have not been read are `None`,
+/// and the corresponding `BatteryState` methods return `Error::FieldNotAvailable`.
+///
+/// Serialized with the field names below. Fields which have not been read are omitted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatterySnapshot {
+    /// Unit: mV
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cell_voltages: Option<Vec<u32>>,
+    /// Positive while charging
+    /// Unit: mA
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<i32>,
+    /// Unit: degC
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Unit: mAh
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_capacity: Option<u32>,
+    /// Unit: mAh
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_charge_capacity: Option<u32>,
+    /// Unit: mAh
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub design_capacity: Option<u32>,
+    /// Unit: %
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub absolute_state_of_charge: Option<u32>,
+    /// Unit: %
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative_state_of_charge: Option<u32>,
+    /// Unit: %
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_of_health: Option<u32>,
+    /// Unit: mV
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bm_voltage: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_status_1: Option<FailStatus1>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_status_2: Option<FailStatus2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_status_3: Option<FailStatus3>,
+}
+
+impl BatterySnapshot {
+    /// Reads the given fields from `state`
+    pub fn try_from_battery_state(
+        state: &(impl BatteryState + ?Sized),
+        fields: &[BatteryField],
+    ) -> Result<Self> {
+        let mut snapshot = Self::default();
+        for &field in fields {
+            snapshot.capture(state, field)?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Reads a field from `state` and stores it
+    pub fn capture(
+        &mut self,
+        state: &(impl BatteryState + ?Sized),
+        field: BatteryField,
+    ) -> Result<()> {
+        match field {
+            BatteryField::CellVoltages => self.cell_voltages = Some(state.cell_voltages()?),
+            BatteryField::Current => self.current = Some(state.current()?),
+            BatteryField::Temperature => self.temperature = Some(state.temperature()?),
+            BatteryField::RemainingCapacity => {
+                self.remaining_capacity = Some(state.remaining_capacity()?)
+            }
+            BatteryField::FullChargeCapacity => {
+                self.full_charge_capacity = Some(state.full_charge_capacity()?)
+            }
+            BatteryField::DesignCapacity => self.design_capacity = Some(state.design_capacity()?),
+            BatteryField::AbsoluteStateOfCharge => {
+                self.absolute_state_of_charge = Some(state.absolute_state_of_charge()?)
+            }
+            BatteryField::RelativeStateOfCharge => {
+                self.relative_state_of_charge = Some(state.relative_state_of_charge()?)
+            }
+            BatteryField::StateOfHealth => self.state_of_health = Some(state.state_of_health()?),
+            BatteryField::BmVoltage => self.bm_voltage = Some(state.bm_voltage()?),
+            BatteryField::FailStatus1 => self.fail_status_1 = Some(state.fail_status_1()?),
+            BatteryField::FailStatus2 => self.fail_status_2 = Some(state.fail_status_2()?),
+            BatteryField::FailStatus3 => self.fail_status_3 = Some(state.fail_status_3()?),
+        }
+        Ok(())
+    }
+
+    /// Returns whether the field has been read
+    pub fn has(&self, field: BatteryField) -> bool {
+        match field {
+            BatteryField::CellVoltages => self.cell_voltages.is_some(),
+            BatteryField::Current => self.current.is_some(),
+            BatteryField::Temperature => self.temperature.is_some(),
+            BatteryField::RemainingCapacity => self.remaining_capacity.is_some(),
+            BatteryField::FullChargeCapacity => self.full_charge_capacity.is_some(),
+            BatteryField::DesignCapacity => self.design_capacity.is_some(),
+            BatteryField::AbsoluteStateOfCharge => self.absolute_state_of_charge.is_some(),
+            BatteryField::RelativeStateOfCharge => self.relative_state_of_charge.is_some(),
+            BatteryField::StateOfHealth => self.state_of_health.is_some(),
+            BatteryField::BmVoltage => self.bm_voltage.is_some(),
+            BatteryField::FailStatus1 => self.fail_status_1.is_some(),
+            BatteryField::FailStatus2 => self.fail_status_2.is_some(),
+            BatteryField::FailStatus3 => self.fail_status_3.is_some(),
+        }
+    }
+
+    /// Returns the fields which have been read
+    pub fn fields(&self) -> Vec<BatteryField> {
+        use strum::IntoEnumIterator;
+        BatteryField::iter()
+            .filter(|&field| self.has(field))
+            .collect()
+    }
+
+    /// Overwrites fields with the ones `other` has
+    pub fn merge(&mut self, other: BatterySnapshot) {
+        macro_rules! merge_fields {
+            ($($field:ident),*) => {
+                $(
+                    if other.$field.is_some() {
+                        self.$field = other.$field;
+                    }
+                )*
+            };
+        }
+        merge_fields!(
+            cell_voltages,
+            current,
+            temperature,
+            remaining_capacity,
+            full_charge_capacity,
+            design_capacity,
+            absolute_state_of_charge,
+            relative_state_of_charge,
+            state_of_health,
+            bm_voltage,
+            fail_status_1,
+            fail_status_2,
+            fail_status_3
+        );
+    }
+}
+
+impl BatteryState for BatterySnapshot {
+    fn cell_voltages(&self) -> Result<Vec<u32>> {
+        self.cell_voltages
+            .clone()
+            .ok_or(Error::FieldNotAvailable(BatteryField::CellVoltages))
+    }
+
+    fn current(&self) -> Result<i32> {
+        self.current
+            .ok_or(Error::FieldNotAvailable(BatteryField::Current))
+    }
+
+    fn temperature(&self) -> Result<f64> {
+        self.temperature
+            .ok_or(Error::FieldNotAvailable(BatteryField::Temperature))
+    }
+
+    fn remaining_capacity(&self) -> Result<u32> {
+        self.remaining_capacity
+            .ok_or(Error::FieldNotAvailable(BatteryField::RemainingCapacity))
+    }
+
+    fn full_charge_capacity(&self) -> Result<u32> {
+        self.full_charge_capacity
+            .ok_or(Error::FieldNotAvailable(BatteryField::FullChargeCapacity))
+    }
+
+    fn design_capacity(&self) -> Result<u32> {
+        self.design_capacity
+            .ok_or(Error::FieldNotAvailable(BatteryField::DesignCapacity))
+    }
+
+    fn absolute_state_of_charge(&self) -> Result<u32> {
+        self.absolute_state_of_charge
+            .ok_or(Error::FieldNotAvailable(
+                BatteryField::AbsoluteStateOfCharge,
+            ))
+    }
+
+    fn relative_state_of_charge(&self) -> Result<u32> {
+        self.relative_state_of_charge
+            .ok_or(Error::FieldNotAvailable(
+                BatteryField::RelativeStateOfCharge,
+            ))
+    }
+
+    fn state_of_health(&self) -> Result<u32> {
+        self.state_of_health
+            .ok_or(Error::FieldNotAvailable(BatteryField::StateOfHealth))
+    }
+
+    fn bm_voltage(&self) -> Result<u32> {
+        self.bm_voltage
+            .ok_or(Error::FieldNotAvailable(BatteryField::BmVoltage))
+    }
+
+    fn fail_status_1(&self) -> Result<FailStatus1> {
+        self.fail_status_1
+            .ok_or(Error::FieldNotAvailable(BatteryField::FailStatus1))
+    }
+
+    fn fail_status_2(&self) -> Result<FailStatus2> {
+        self.fail_status_2
+            .ok_or(Error::FieldNotAvailable(BatteryField::FailStatus2))
+    }
+
+    fn fail_status_3(&self) -> Result<FailStatus3> {
+        self.fail_status_3
+            .ok_or(Error::FieldNotAvailable(BatteryField::FailStatus3))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_from_battery_state() {
+        let source = BatterySnapshot {
+            current: Some(1500),
+            bm_voltage: Some(28800),
+            fail_status_1: Some(FailStatus1(0x40)),
+            ..Default::default()
+        };
+
+        let snapshot = BatterySnapshot::try_from_battery_state(
+            &source,
+            &[BatteryField::Current, BatteryField::FailStatus1],
+        )
+        .unwrap();
+        assert_eq!(snapshot.current().unwrap(), 1500);
+        assert_eq!(snapshot.fail_status_1().unwrap(), FailStatus1(0x40));
+        assert!(snapshot.bm_voltage().is_err());
+        assert_eq!(
+            snapshot.fields(),
+            vec![BatteryField::Current, BatteryField::FailStatus1]
+        );
+
+        let result = BatterySnapshot::try_from_battery_state(&source, &[BatteryField::Temperature]);
+        assert!(matches!(
+            result,
+            Err(Error::FieldNotAvailable(BatteryField::Temperature))
+        ));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut snapshot = BatterySnapshot {
+            current: Some(1500),
+            temperature: Some(25.0),
+            ..Default::default()
+        };
+        snapshot.merge(BatterySnapshot {
+            current: Some(-300),
+            cell_voltages: Some(vec![3600; 8]),
+            ..Default::default()
+        });
+        assert_eq!(snapshot.current, Some(-300));
+        assert_eq!(snapshot.temperature, Some(25.0));
+        assert_eq!(snapshot.cell_voltages, Some(vec![3600; 8]));
+        assert!(!snapshot.has(BatteryField::BmVoltage));
+    }
+
+    #[test]
+    fn test_serde() {
+        let snapshot = BatterySnapshot {
+            current: Some(-1500),
+            cell_voltages: Some(vec![3300; 2]),
+            fail_status_2: Some(FailStatus2(0x40)),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            json,
+            r#"{"cell_voltages":[3300,3300],"current":-1500,"fail_status_2":64}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<BatterySnapshot>(&json).unwrap(),
+            snapshot
+        );
+        assert_eq!(
+            serde_json::from_str::<BatterySnapshot>("{}").unwrap(),
+            BatterySnapshot::default()
+        );
+    }
+}
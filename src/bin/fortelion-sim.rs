@@ -0,0 +1,259 @@
+//! Virtual FORTELION battery module on a pseudo-terminal
+//!
+//! Prints the path of the slave side, which can be opened with `Port::try_new`.
+
+#[cfg(unix)]
+use std::{
+    io::ErrorKind,
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use fortelion::{
+    fault_injection::{FaultScript, FaultyModule, FaultyTransport, LinkFault, Trigger},
+    simulator::{Simulator, SimulatorConfig},
+    uart::Responder,
+    Error, FailStatusItem,
+};
+#[cfg(unix)]
+use serialport::{SerialPort, TTYPort};
+
+#[cfg(unix)]
+fn main() {
+    const POLLING_INTERVAL: Duration = Duration::from_millis(100);
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{message}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let (mut master, slave) = TTYPort::pair().expect("failed to create a pseudo-terminal");
+    master
+        .set_timeout(POLLING_INTERVAL)
+        .expect("failed to set timeout");
+    println!("{}", slave.name().expect("pseudo-terminal has no name"));
+
+    let mut responder = Responder::new(
+        FaultyTransport::new(master, args.link_faults),
+        FaultyModule::new(Simulator::new(args.config), args.data_faults),
+    );
+    let mut last_step = Instant::now();
+    loop {
+        let now = Instant::now();
+        responder
+            .module_mut()
+            .inner_mut()
+            .step((now - last_step).mul_f64(args.time_scale));
+        last_step = now;
+
+        match responder.respond_once() {
+            Ok(_) => {}
+            Err(Error::UartFailedToReceive(e))
+                if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::BrokenPipe) =>
+            {
+                // no request, or the host side is not open
+                if e.kind() == ErrorKind::BrokenPipe {
+                    std::thread::sleep(POLLING_INTERVAL);
+                }
+            }
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("fortelion-sim requires pseudo-terminal support");
+    std::process::exit(1);
+}
+
+#[cfg(unix)]
+const USAGE: &str = "Usage: fortelion-sim [OPTIONS]
+
+Options:
+  --load-current <mA>       Current into the module, negative while discharging [default: -5000]
+  --initial-soc <%>         Initial relative state of charge [default: 80]
+  --design-capacity <mAh>   Design capacity [default: 10000]
+  --state-of-health <%>     State of health [default: 100]
+  --temperature <degC>      Temperature [default: 25]
+  --time-scale <factor>     Speed of simulated time relative to wall clock (0 - 1000000) [default: 1]
+  --link-fault <fault>      Injects a fault into responses, may be repeated
+                            <fault>: drop-byte|corrupt-checksum|wrong-bm-id|truncate|delay|duplicate
+  --data-fault <item>       Raises a fail status bit, may be repeated
+                            <item>: name of `FailStatusItem` (e.g. FuseBlown)
+
+Faults are injected always, with `<fault>:<probability>` by probability (0.0 - 1.0),
+or with `<fault>@<from>-<until>` between seconds after start.";
+
+/// Fastest simulated time relative to wall clock
+#[cfg(unix)]
+const MAX_TIME_SCALE: f64 = 1_000_000.0;
+
+#[cfg(unix)]
+struct Args {
+    config: SimulatorConfig,
+    time_scale: f64,
+    link_faults: FaultScript<LinkFault>,
+    data_faults: FaultScript<FailStatusItem>,
+}
+
+#[cfg(unix)]
+fn parse_fault<F>(arg: &str, parse: impl Fn(&str) -> Option<F>) -> Result<(F, Trigger), String> {
+    let invalid = || format!("invalid fault: {arg}");
+    let (name, trigger) = if let Some((name, probability)) = arg.split_once(':') {
+        let probability: f64 = probability.parse().map_err(|_| invalid())?;
+        if !(0.0..=1.0).contains(&probability) {
+            return Err(invalid());
+        }
+        (name, Trigger::Probability(probability))
+    } else if let Some((name, range)) = arg.split_once('@') {
+        let (from, until) = range.split_once('-').ok_or_else(invalid)?;
+        let seconds = |value: &str| {
+            value
+                .parse()
+                .ok()
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .ok_or_else(invalid)
+        };
+        let (from, until) = (seconds(from)?, seconds(until)?);
+        if from > until {
+            return Err(invalid());
+        }
+        (name, Trigger::Timeline { from, until })
+    } else {
+        (arg, Trigger::Always)
+    };
+    Ok((parse(name).ok_or_else(invalid)?, trigger))
+}
+
+#[cfg(unix)]
+fn parse_link_fault(name: &str) -> Option<LinkFault> {
+    match name {
+        "drop-byte" => Some(LinkFault::DropBytes(1)),
+        "corrupt-checksum" => Some(LinkFault::CorruptChecksum),
+        "wrong-bm-id" => Some(LinkFault::WrongBmId(0x02)),
+        "truncate" => Some(LinkFault::Truncate(4)),
+        "delay" => Some(LinkFault::Delay(Duration::from_millis(500))),
+        "duplicate" => Some(LinkFault::Duplicate),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        fn value<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+            value
+                .ok_or_else(|| format!("missing value for {name}"))?
+                .parse()
+                .map_err(|_| format!("invalid value for {name}"))
+        }
+
+        let mut parsed = Self {
+            config: Default::default(),
+            time_scale: 1.0,
+            link_faults: Default::default(),
+            data_faults: Default::default(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--load-current" => parsed.config.load_current = value(&arg, args.next())?,
+                "--initial-soc" => {
+                    parsed.config.initial_state_of_charge = value(&arg, args.next())?
+                }
+                "--design-capacity" => parsed.config.design_capacity = value(&arg, args.next())?,
+                "--state-of-health" => parsed.config.state_of_health = value(&arg, args.next())?,
+                "--temperature" => parsed.config.temperature = value(&arg, args.next())?,
+                "--time-scale" => {
+                    let time_scale: f64 = value(&arg, args.next())?;
+                    if !(0.0..=MAX_TIME_SCALE).contains(&time_scale) {
+                        return Err(format!("invalid value for {arg}"));
+                    }
+                    parsed.time_scale = time_scale;
+                }
+                "--link-fault" => {
+                    let fault: String = value(&arg, args.next())?;
+                    let (fault, trigger) = parse_fault(&fault, parse_link_fault)?;
+                    parsed.link_faults = parsed.link_faults.add(fault, trigger);
+                }
+                "--data-fault" => {
+                    let fault: String = value(&arg, args.next())?;
+                    let (item, trigger) = parse_fault(&fault, |name| name.parse().ok())?;
+                    parsed.data_faults = parsed.data_faults.add(item, trigger);
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ => return Err(format!("unknown argument: {arg}")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_time_scale() {
+        assert_eq!(parse(&[]).unwrap().time_scale, 1.0);
+        assert_eq!(parse(&["--time-scale", "60"]).unwrap().time_scale, 60.0);
+        assert_eq!(parse(&["--time-scale", "0"]).unwrap().time_scale, 0.0);
+        for invalid in ["-1", "NaN", "inf", "1e300", "fast"] {
+            assert!(parse(&["--time-scale", invalid]).is_err(), "{invalid}");
+        }
+        assert!(parse(&["--time-scale"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_fault() {
+        assert_eq!(
+            parse_fault("duplicate", parse_link_fault).unwrap(),
+            (LinkFault::Duplicate, Trigger::Always)
+        );
+        assert_eq!(
+            parse_fault("duplicate:0.5", parse_link_fault).unwrap(),
+            (LinkFault::Duplicate, Trigger::Probability(0.5))
+        );
+        assert_eq!(
+            parse_fault("duplicate@10-20.5", parse_link_fault).unwrap(),
+            (
+                LinkFault::Duplicate,
+                Trigger::Timeline {
+                    from: Duration::from_secs(10),
+                    until: Duration::from_secs_f64(20.5),
+                }
+            )
+        );
+        for invalid in [
+            "duplicate:5",
+            "duplicate:-1",
+            "duplicate:NaN",
+            "duplicate@20-10",
+            "duplicate@-1-10",
+            "duplicate@10",
+            "duplicate@0-1e300",
+            "unknown",
+        ] {
+            assert!(parse_fault(invalid, parse_link_fault).is_err(), "{invalid}");
+        }
+
+        let mut args = parse(&["--data-fault", "FuseBlown@0-60"]).unwrap();
+        assert!(args.link_faults.next_faults().is_empty());
+        assert_eq!(
+            args.data_faults.next_faults(),
+            vec![FailStatusItem::FuseBlown]
+        );
+        assert!(parse(&["--data-fault", "NoSuchItem"]).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+use crate::{battery_state::BatteryState, error::Result, fail_status::FailState};
+
+/// Serialized in snake case, e.g. `"constant_voltage"`
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChargePhase {
+    Idle,
+    Discharging,
+    /// Charging while the voltage is below the constant voltage threshold
+    ConstantCurrent,
+    /// Charging while the voltage is held at the constant voltage threshold
+    ConstantVoltage,
+    /// `FailStatusItem::FullyChargeDetection` is set and the module is not discharging
+    ToppedOff,
+    /// Charging shortly after discharging, e.g. braking
+    Regenerating,
+}
+
+impl ChargePhase {
+    pub fn is_charging(&self) -> bool {
+        matches!(
+            self,
+            ChargePhase::ConstantCurrent | ChargePhase::ConstantVoltage
+        )
+    }
+}
+
+/// A change of `ChargePhase` between successive readings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChargePhaseEvent {
+    pub from: ChargePhase,
+    pub to: ChargePhase,
+    /// How long the module was in `from`, or `None` if it was the initial phase
+    pub duration: Option<Duration>,
+    pub at: SystemTime,
+}
+
+/// Classifies successive readings into `ChargePhase`s
+///
+/// The module leaves `Idle` when the magnitude of the current reaches the idle current,
+/// and goes back once it falls below the idle current minus the current hysteresis.
+/// Charging turns from constant current to constant voltage when `bm_voltage` reaches
+/// the constant voltage threshold, and back once it falls below the threshold minus
+/// the voltage hysteresis. Charging which starts right after discharging is
+/// `Regenerating` until it lasts longer than the regeneration limit.
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use fortelion::{charge_phase::*, BatterySnapshot};
+///
+/// let mut detector = ChargePhaseDetector::new();
+/// let t0 = SystemTime::UNIX_EPOCH;
+/// let state = BatterySnapshot {
+///     current: Some(-20000),
+///     ..Default::default()
+/// };
+/// let event = detector.update(&state, t0).unwrap().unwrap();
+/// assert_eq!(event.to, ChargePhase::Discharging);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChargePhaseDetector {
+    idle_current: u32,
+    current_hysteresis: u32,
+    constant_voltage_threshold: u32,
+    voltage_hysteresis: u32,
+    max_regeneration: Duration,
+    phase: ChargePhase,
+    initial: bool,
+    since: Option<SystemTime>,
+    // when the current last turned positive
+    charging_since: Option<SystemTime>,
+}
+
+impl Default for ChargePhaseDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChargePhaseDetector {
+    pub fn new() -> Self {
+        Self {
+            idle_current: 500,
+            current_hysteresis: 200,
+            constant_voltage_threshold: 28000,
+            voltage_hysteresis: 200,
+            max_regeneration: Duration::from_secs(10),
+            phase: ChargePhase::Idle,
+            initial: true,
+            since: None,
+            charging_since: None,
+        }
+    }
+
+    /// Sets the magnitude of the current at which the module is no longer idle (default: 500)
+    /// Unit: mA
+    pub fn with_idle_current(mut self, current: u32) -> Self {
+        self.idle_current = current;
+        self
+    }
+
+    /// Sets how far below the idle current the module goes back to idle (default: 200)
+    /// Unit: mA
+    pub fn with_current_hysteresis(mut self, hysteresis: u32) -> Self {
+        self.current_hysteresis = hysteresis;
+        self
+    }
+
+    /// Sets the module voltage at which charging turns to constant voltage (default: 28000)
+    /// Unit: mV
+    pub fn with_constant_voltage_threshold(mut self, voltage: u32) -> Self {
+        self.constant_voltage_threshold = voltage;
+        self
+    }
+
+    /// Sets how far below the constant voltage threshold charging turns back to constant current (default: 200)
+    /// Unit: mV
+    pub fn with_voltage_hysteresis(mut self, hysteresis: u32) -> Self {
+        self.voltage_hysteresis = hysteresis;
+        self
+    }
+
+    /// Sets how long charging right after discharging counts as regenerating (default: 10 s)
+    pub fn with_max_regeneration(mut self, duration: Duration) -> Self {
+        self.max_regeneration = duration;
+        self
+    }
+
+    /// Returns the current phase
+    pub fn phase(&self) -> ChargePhase {
+        self.phase
+    }
+
+    /// Returns when the current phase was entered
+    pub fn since(&self) -> Option<SystemTime> {
+        self.since
+    }
+
+    /// Feeds `current`, and if available `bm_voltage` and the fail status, of `state` read at `at`
+    /// Returns an event if the phase changed.
+    pub fn update(
+        &mut self,
+        state: &(impl BatteryState + ?Sized),
+        at: SystemTime,
+    ) -> Result<Option<ChargePhaseEvent>> {
+        let current = state.current()?;
+        let voltage = state.bm_voltage().ok();
+        let fully_charged = state
+            .fail_status_1()
+            .is_ok_and(|status| status.fully_charge_detection() == FailState::Ng);
+        Ok(self.update_sample(current, voltage, fully_charged, at))
+    }
+
+    /// Feeds a current (mA, positive while charging), a module voltage (mV) and
+    /// whether full charge is detected, read at `at`
+    /// Returns an event if the phase changed.
+    pub fn update_sample(
+        &mut self,
+        current: i32,
+        voltage: Option<u32>,
+        fully_charged: bool,
+        at: SystemTime,
+    ) -> Option<ChargePhaseEvent> {
+        let next = self.classify(current, voltage, fully_charged, at);
+        if next == self.phase && !self.initial {
+            return None;
+        }
+        let event = ChargePhaseEvent {
+            from: self.phase,
+            to: next,
+            duration: self
+                .since
+                .filter(|_| !self.initial)
+                .and_then(|since| at.duration_since(since).ok()),
+            at,
+        };
+        let initial = std::mem::replace(&mut self.initial, false);
+        self.phase = next;
+        self.since = Some(at);
+        (!initial || next != ChargePhase::Idle).then_some(event)
+    }
+
+    fn classify(
+        &mut self,
+        current: i32,
+        voltage: Option<u32>,
+        fully_charged: bool,
+        at: SystemTime,
+    ) -> ChargePhase {
+        let active = if self.phase == ChargePhase::Idle || self.initial {
+            current.unsigned_abs() >= self.idle_current
+        } else {
+            current.unsigned_abs() >= self.idle_current.saturating_sub(self.current_hysteresis)
+        };
+        let charging = active && current > 0;
+        if !charging {
+            self.charging_since = None;
+        }
+
+        if fully_charged && (current >= 0 || !active) {
+            return ChargePhase::ToppedOff;
+        }
+        if !active {
+            return ChargePhase::Idle;
+        }
+        if current < 0 {
+            return ChargePhase::Discharging;
+        }
+
+        let charging_since = *self.charging_since.get_or_insert(at);
+        let regenerating = matches!(
+            self.phase,
+            ChargePhase::Discharging | ChargePhase::Regenerating
+        ) && at.duration_since(charging_since).unwrap_or_default()
+            <= self.max_regeneration;
+        if regenerating {
+            return ChargePhase::Regenerating;
+        }
+
+        match voltage {
+            Some(voltage) if voltage >= self.constant_voltage_threshold => {
+                ChargePhase::ConstantVoltage
+            }
+            Some(voltage)
+                if self.phase == ChargePhase::ConstantVoltage
+                    && voltage
+                        >= self
+                            .constant_voltage_threshold
+                            .saturating_sub(self.voltage_hysteresis) =>
+            {
+                ChargePhase::ConstantVoltage
+            }
+            Some(_) => ChargePhase::ConstantCurrent,
+            // without a voltage, keep what is known
+            None if self.phase.is_charging() => self.phase,
+            None => ChargePhase::ConstantCurrent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{battery_snapshot::BatterySnapshot, fail_status::FailStatus1};
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn phases(
+        detector: &mut ChargePhaseDetector,
+        samples: &[(i32, u32, bool)],
+    ) -> Vec<ChargePhase> {
+        samples
+            .iter()
+            .enumerate()
+            .filter_map(|(secs, (current, voltage, fully_charged))| {
+                detector.update_sample(*current, Some(*voltage), *fully_charged, at(secs as u64))
+            })
+            .map(|event| event.to)
+            .collect()
+    }
+
+    #[test]
+    fn test_charge_session() {
+        let mut detector = ChargePhaseDetector::new();
+        let events = phases(
+            &mut detector,
+            &[
+                (0, 26000, false),
+                (20000, 27000, false),
+                (20000, 28000, false),
+                // within the voltage hysteresis
+                (10000, 27900, false),
+                (1000, 28000, false),
+                (0, 28000, true),
+                (-20000, 27500, false),
+                // within the current hysteresis
+                (-400, 27500, false),
+                (-100, 27500, false),
+            ],
+        );
+        assert_eq!(
+            events,
+            vec![
+                ChargePhase::ConstantCurrent,
+                ChargePhase::ConstantVoltage,
+                ChargePhase::ToppedOff,
+                ChargePhase::Discharging,
+                ChargePhase::Idle,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_regeneration() {
+        let mut detector = ChargePhaseDetector::new().with_max_regeneration(Duration::from_secs(3));
+        let mut samples = vec![(-20000, 26000, false); 3];
+        samples.extend([(5000, 26500, false); 6]);
+        let events = phases(&mut detector, &samples);
+        assert_eq!(
+            events,
+            vec![
+                ChargePhase::Discharging,
+                ChargePhase::Regenerating,
+                ChargePhase::ConstantCurrent,
+            ]
+        );
+        assert_eq!(detector.since(), Some(at(7)));
+    }
+
+    #[test]
+    fn test_event_duration() {
+        let mut detector = ChargePhaseDetector::new();
+        let mut state = BatterySnapshot {
+            current: Some(-20000),
+            bm_voltage: Some(26000),
+            fail_status_1: Some(FailStatus1(0)),
+            ..Default::default()
+        };
+        let first = detector.update(&state, at(0)).unwrap().unwrap();
+        assert_eq!(first.from, ChargePhase::Idle);
+        assert_eq!(first.duration, None);
+        assert_eq!(detector.update(&state, at(5)).unwrap(), None);
+
+        state.current = Some(0);
+        let event = detector.update(&state, at(30)).unwrap().unwrap();
+        assert_eq!(event.from, ChargePhase::Discharging);
+        assert_eq!(event.to, ChargePhase::Idle);
+        assert_eq!(event.duration, Some(Duration::from_secs(30)));
+
+        assert!(detector
+            .update(&BatterySnapshot::default(), at(31))
+            .is_err());
+    }
+}
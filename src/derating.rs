@@ -0,0 +1,402 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battery_module::BatteryField,
+    battery_state::BatteryState,
+    error::{Error, Result},
+};
+
+/// Factor applied to a current limit against an input value
+/// Points are `(input, factor)` sorted by input, and the factor between them is
+/// linearly interpolated. Outside the points, the factor of the nearest one applies.
+/// Serialized as an array of `[input, factor]` pairs, validated when deserialized
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<(f64, f64)>", into = "Vec<(f64, f64)>")]
+pub struct DeratingCurve(Vec<(f64, f64)>);
+
+impl DeratingCurve {
+    /// Fails unless every value is finite and the inputs are strictly increasing
+    pub fn new(points: Vec<(f64, f64)>) -> Result<Self> {
+        if let Some((input, factor)) = points
+            .iter()
+            .find(|(input, factor)| !input.is_finite() || !factor.is_finite())
+        {
+            return Err(Error::InvalidDeratingCurve(format!(
+                "point ({input}, {factor}) is not finite"
+            )));
+        }
+        if let Some(pair) = points.windows(2).find(|pair| pair[0].0 >= pair[1].0) {
+            return Err(Error::InvalidDeratingCurve(format!(
+                "input {} does not increase after {}",
+                pair[1].0, pair[0].0
+            )));
+        }
+        Ok(Self(points))
+    }
+
+    /// Returns the `(input, factor)` points
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.0
+    }
+
+    /// Returns the factor at `input`, clamped to 0.0..=1.0
+    /// An empty curve does not derate.
+    pub fn factor(&self, input: f64) -> f64 {
+        let points = &self.0;
+        let factor = match points.iter().position(|(x, _)| *x >= input) {
+            None => points.last().map(|(_, f)| *f).unwrap_or(1.0),
+            Some(0) => points[0].1,
+            Some(i) => {
+                let (x0, f0) = points[i - 1];
+                let (x1, f1) = points[i];
+                f0 + (f1 - f0) * (input - x0) / (x1 - x0)
+            }
+        };
+        factor.clamp(0.0, 1.0)
+    }
+}
+
+impl TryFrom<Vec<(f64, f64)>> for DeratingCurve {
+    type Error = Error;
+
+    fn try_from(points: Vec<(f64, f64)>) -> Result<Self> {
+        Self::new(points)
+    }
+}
+
+impl From<DeratingCurve> for Vec<(f64, f64)> {
+    fn from(curve: DeratingCurve) -> Self {
+        curve.0
+    }
+}
+
+/// Limits and derating curves of `DeratingAdvisor`
+/// Missing fields take the default values when deserialized.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeratingConfig {
+    /// Discharge current which can be drawn indefinitely, below the 65 A detection
+    /// Unit: mA
+    pub continuous_discharge_current: u32,
+    /// Discharge current which can be drawn briefly, below the 90 A detection
+    /// Unit: mA
+    pub peak_discharge_current: u32,
+    /// Charge current which can be fed indefinitely, below the 45 A detection
+    /// Unit: mA
+    pub continuous_charge_current: u32,
+    /// Charge current which can be fed briefly, below the 65 A detection
+    /// Unit: mA
+    pub peak_charge_current: u32,
+    /// Fraction of each limit kept clear of the protection, e.g. 0.1 for 10 %
+    pub margin: f64,
+    /// Against temperature (degC)
+    pub discharge_temperature_curve: DeratingCurve,
+    /// Against temperature (degC)
+    pub charge_temperature_curve: DeratingCurve,
+    /// Against relative state of charge (%)
+    pub discharge_state_of_charge_curve: DeratingCurve,
+    /// Against relative state of charge (%)
+    pub charge_state_of_charge_curve: DeratingCurve,
+    /// Against the lowest cell voltage (mV)
+    pub discharge_cell_voltage_curve: DeratingCurve,
+    /// Against the highest cell voltage (mV)
+    pub charge_cell_voltage_curve: DeratingCurve,
+}
+
+impl Default for DeratingConfig {
+    fn default() -> Self {
+        Self {
+            continuous_discharge_current: 65000,
+            peak_discharge_current: 90000,
+            continuous_charge_current: 45000,
+            peak_charge_current: 65000,
+            margin: 0.1,
+            discharge_temperature_curve: DeratingCurve(vec![
+                (-20.0, 0.0),
+                (-10.0, 0.5),
+                (0.0, 0.8),
+                (10.0, 1.0),
+                (45.0, 1.0),
+                (55.0, 0.5),
+                (60.0, 0.0),
+            ]),
+            charge_temperature_curve: DeratingCurve(vec![
+                (0.0, 0.0),
+                (5.0, 0.3),
+                (15.0, 1.0),
+                (40.0, 1.0),
+                (45.0, 0.5),
+                (50.0, 0.0),
+            ]),
+            discharge_state_of_charge_curve: DeratingCurve(vec![
+                (0.0, 0.0),
+                (5.0, 0.3),
+                (20.0, 1.0),
+            ]),
+            charge_state_of_charge_curve: DeratingCurve(vec![
+                (80.0, 1.0),
+                (95.0, 0.3),
+                (100.0, 0.1),
+            ]),
+            discharge_cell_voltage_curve: DeratingCurve(vec![
+                (2800.0, 0.0),
+                (3000.0, 0.5),
+                (3100.0, 1.0),
+            ]),
+            charge_cell_voltage_curve: DeratingCurve(vec![
+                (3450.0, 1.0),
+                (3550.0, 0.5),
+                (3650.0, 0.0),
+            ]),
+        }
+    }
+}
+
+/// Input which derated a limit the most
+/// Serialized in snake case, e.g. `"state_of_charge"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitingFactor {
+    Temperature,
+    StateOfCharge,
+    CellVoltage,
+}
+
+/// Recommended current limits
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CurrentLimits {
+    /// Unit: mA
+    pub continuous_discharge: u32,
+    /// Unit: mA
+    pub peak_discharge: u32,
+    /// Unit: mA
+    pub continuous_charge: u32,
+    /// Unit: mA
+    pub peak_charge: u32,
+    /// `None` if discharging is not derated
+    pub discharge_limited_by: Option<LimitingFactor>,
+    /// `None` if charging is not derated
+    pub charge_limited_by: Option<LimitingFactor>,
+}
+
+/// Recommends charge and discharge current limits which keep the module clear of
+/// its over-current protections
+///
+/// The limits below the protections are scaled down by the margin, then by the
+/// smallest factor of the temperature, state of charge and cell voltage curves.
+///
+/// ```
+/// use fortelion::{derating::*, BatterySnapshot};
+///
+/// let advisor = DeratingAdvisor::new();
+/// let state = BatterySnapshot {
+///     temperature: Some(25.0),
+///     relative_state_of_charge: Some(50),
+///     cell_voltages: Some(vec![3300; 8]),
+///     ..Default::default()
+/// };
+/// let limits = advisor.advise(&state).unwrap();
+/// assert_eq!(limits.continuous_discharge, 58500);
+/// assert_eq!(limits.discharge_limited_by, None);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DeratingAdvisor {
+    config: DeratingConfig,
+}
+
+impl DeratingAdvisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(mut self, config: DeratingConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &DeratingConfig {
+        &self.config
+    }
+
+    /// Recommends limits from `temperature`, `relative_state_of_charge` and `cell_voltages` of `state`
+    pub fn advise(&self, state: &(impl BatteryState + ?Sized)) -> Result<CurrentLimits> {
+        let temperature = state.temperature()?;
+        let state_of_charge = state.relative_state_of_charge()? as f64;
+        let cell_voltages = state.cell_voltages()?;
+        let (Some(min_cell_voltage), Some(max_cell_voltage)) =
+            (cell_voltages.iter().min(), cell_voltages.iter().max())
+        else {
+            return Err(Error::FieldNotAvailable(BatteryField::CellVoltages));
+        };
+        Ok(self.advise_values(
+            temperature,
+            state_of_charge,
+            *min_cell_voltage,
+            *max_cell_voltage,
+        ))
+    }
+
+    /// Recommends limits from a temperature (degC), a relative state of charge (%)
+    /// and the lowest and highest cell voltages (mV)
+    pub fn advise_values(
+        &self,
+        temperature: f64,
+        state_of_charge: f64,
+        min_cell_voltage: u32,
+        max_cell_voltage: u32,
+    ) -> CurrentLimits {
+        let config = &self.config;
+        let (discharge_factor, discharge_limited_by) = lowest([
+            (
+                LimitingFactor::Temperature,
+                config.discharge_temperature_curve.factor(temperature),
+            ),
+            (
+                LimitingFactor::StateOfCharge,
+                config
+                    .discharge_state_of_charge_curve
+                    .factor(state_of_charge),
+            ),
+            (
+                LimitingFactor::CellVoltage,
+                config
+                    .discharge_cell_voltage_curve
+                    .factor(min_cell_voltage as f64),
+            ),
+        ]);
+        let (charge_factor, charge_limited_by) = lowest([
+            (
+                LimitingFactor::Temperature,
+                config.charge_temperature_curve.factor(temperature),
+            ),
+            (
+                LimitingFactor::StateOfCharge,
+                config.charge_state_of_charge_curve.factor(state_of_charge),
+            ),
+            (
+                LimitingFactor::CellVoltage,
+                config
+                    .charge_cell_voltage_curve
+                    .factor(max_cell_voltage as f64),
+            ),
+        ]);
+        let limit = |current: u32, factor: f64| {
+            (current as f64 * (1.0 - config.margin).clamp(0.0, 1.0) * factor).round() as u32
+        };
+        CurrentLimits {
+            continuous_discharge: limit(config.continuous_discharge_current, discharge_factor),
+            peak_discharge: limit(config.peak_discharge_current, discharge_factor),
+            continuous_charge: limit(config.continuous_charge_current, charge_factor),
+            peak_charge: limit(config.peak_charge_current, charge_factor),
+            discharge_limited_by,
+            charge_limited_by,
+        }
+    }
+}
+
+fn lowest(factors: [(LimitingFactor, f64); 3]) -> (f64, Option<LimitingFactor>) {
+    factors
+        .into_iter()
+        .fold((1.0, None), |(lowest, limited_by), (input, factor)| {
+            if factor < lowest {
+                (factor, Some(input))
+            } else {
+                (lowest, limited_by)
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    #[test]
+    fn test_curve() {
+        let curve = DeratingCurve(vec![(0.0, 0.0), (10.0, 1.0), (20.0, 1.0), (30.0, 0.5)]);
+        assert_eq!(curve.factor(-5.0), 0.0);
+        assert_eq!(curve.factor(5.0), 0.5);
+        assert_eq!(curve.factor(15.0), 1.0);
+        assert_eq!(curve.factor(25.0), 0.75);
+        assert_eq!(curve.factor(100.0), 0.5);
+        assert_eq!(DeratingCurve(vec![]).factor(0.0), 1.0);
+    }
+
+    #[test]
+    fn test_invalid_curve() {
+        assert!(DeratingCurve::new(vec![(0.0, 1.0), (10.0, 0.5)]).is_ok());
+        assert!(matches!(
+            DeratingCurve::new(vec![(10.0, 1.0), (0.0, 0.5)]),
+            Err(Error::InvalidDeratingCurve(_))
+        ));
+        assert!(DeratingCurve::new(vec![(0.0, 1.0), (0.0, 0.5)]).is_err());
+        assert!(DeratingCurve::new(vec![(0.0, f64::NAN)]).is_err());
+
+        let curve: DeratingCurve = serde_json::from_str("[[0.0, 0.0], [10.0, 1.0]]").unwrap();
+        assert_eq!(curve.points(), &[(0.0, 0.0), (10.0, 1.0)]);
+        assert_eq!(
+            serde_json::to_string(&curve).unwrap(),
+            "[[0.0,0.0],[10.0,1.0]]"
+        );
+        assert!(serde_json::from_str::<DeratingCurve>("[[10.0, 0.0], [0.0, 1.0]]").is_err());
+    }
+
+    #[test]
+    fn test_nominal() {
+        let limits = DeratingAdvisor::new().advise_values(25.0, 50.0, 3300, 3310);
+        assert_eq!(
+            limits,
+            CurrentLimits {
+                continuous_discharge: 58500,
+                peak_discharge: 81000,
+                continuous_charge: 40500,
+                peak_charge: 58500,
+                discharge_limited_by: None,
+                charge_limited_by: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_derated() {
+        let advisor = DeratingAdvisor::new().with_config(DeratingConfig {
+            margin: 0.0,
+            ..Default::default()
+        });
+        // cold: charging is stopped, discharging is halved
+        let limits = advisor.advise_values(-10.0, 50.0, 3300, 3300);
+        assert_eq!(limits.continuous_charge, 0);
+        assert_eq!(limits.charge_limited_by, Some(LimitingFactor::Temperature));
+        assert_eq!(limits.continuous_discharge, 32500);
+        assert_eq!(limits.peak_discharge, 45000);
+
+        // nearly empty: the lowest cell voltage limits discharging
+        let limits = advisor.advise_values(25.0, 10.0, 2900, 3000);
+        assert_eq!(limits.continuous_discharge, 16250);
+        assert_eq!(
+            limits.discharge_limited_by,
+            Some(LimitingFactor::CellVoltage)
+        );
+        assert_eq!(limits.continuous_charge, 45000);
+
+        // nearly full
+        let limits = advisor.advise_values(25.0, 95.0, 3400, 3550);
+        assert_eq!(limits.continuous_charge, 13500);
+        assert_eq!(
+            limits.charge_limited_by,
+            Some(LimitingFactor::StateOfCharge)
+        );
+
+        assert!(advisor.advise(&BatterySnapshot::default()).is_err());
+        let state = BatterySnapshot {
+            temperature: Some(25.0),
+            relative_state_of_charge: Some(50),
+            cell_voltages: Some(vec![]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            advisor.advise(&state),
+            Err(Error::FieldNotAvailable(BatteryField::CellVoltages))
+        ));
+    }
+}
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use thiserror::Error;
 
-use crate::uart;
+use crate::{battery_module::BatteryField, uart};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -18,6 +18,12 @@ pub enum Error {
     UartFailedToReceive(std::io::Error),
     #[error("fortelion: Invalid data frame {:?}", .0)]
     InvalidUartDataFrame(String),
+    #[error("fortelion: Invalid command frame {:?}", .0)]
+    InvalidUartCommandFrame(String),
+    #[error("fortelion: Unknown command {:#04x}", .0)]
+    UnknownCommand(u8),
+    #[error("fortelion: Command `{:?}` is not supported", .0)]
+    UnsupportedCommand(uart::Command),
     #[error(
         "fortelion: Uart Data frame has no appropriate data: response command is `{:?}`, must be any of {:?}",
         response_command,
@@ -29,6 +35,12 @@ pub enum Error {
     },
     #[error("fortelion: Data bytes shortage {:?}", .0)]
     DataBytesShortage(String),
+    #[error("fortelion: Field `{:?}` is not available", .0)]
+    FieldNotAvailable(BatteryField),
+    #[error("fortelion: Field `{:?}` is not supported by the battery module", .0)]
+    UnsupportedField(BatteryField),
+    #[error("fortelion: Invalid derating curve {:?}", .0)]
+    InvalidDeratingCurve(String),
 }
 
 pub type Result<T> = ::std::result::Result<T, Error>;
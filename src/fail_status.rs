@@ -1,8 +1,16 @@
-use strum::{Display, EnumIter, IntoEnumIterator};
-
-use crate::BatteryState;
-
-#[derive(Debug, Default, PartialEq)]
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+
+use crate::{
+    error::Result,
+    fail_status_reading::{FailStatusReading, UnavailableReason},
+    fault_set::FaultSet,
+    BatteryState,
+};
+
+/// Serialized as `"ok"`, `"ng"` or `"unknown"`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FailState {
     Ok,
     Ng,
@@ -10,8 +18,15 @@ pub enum FailState {
     Unknown,
 }
 
+/// Fail status bytes are serialized as the raw byte, e.g. `64`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct FailStatus1(pub u8);
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct FailStatus2(pub u8);
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct FailStatus3(pub u8);
 
 const FAIL_STATUS_1_OVER_CURRENT_DISCHARGE_DETECTION_65A_BIT: usize = 0;
@@ -173,17 +188,27 @@ impl FailStatus3 {
     }
 }
 
-#[derive(Clone, Copy, Debug, Display, EnumIter, PartialEq)]
+/// Serialized in snake case, e.g. `"over_current_discharge_detection_65a"` or `"fuse_blown"`
+#[derive(
+    Clone, Copy, Debug, Display, EnumIter, EnumString, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum FailStatusItem {
+    #[serde(rename = "over_current_discharge_detection_65a")]
     OverCurrentDischargeDetection65A,
+    #[serde(rename = "over_current_discharge_detection_90a")]
     OverCurrentDischargeDetection90A,
     OverChargeProtection,
+    #[serde(rename = "over_current_charge_detection_45a")]
     OverCurrentChargeDetection45A,
     OverTemperatureDischargeDetection,
     LowVoltageDetection,
     FullyChargeDetection,
+    #[serde(rename = "over_current_discharge_detection_200a")]
     OverCurrentDischargeDetection200A,
+    #[serde(rename = "over_current_discharge_detection_110a")]
     OverCurrentDischargeDetection110A,
+    #[serde(rename = "over_current_charge_detection_65a")]
     OverCurrentChargeDetection65A,
     OverTemperatureChargeDetection,
     CellUnbalanceDetection,
@@ -201,6 +226,56 @@ pub enum FailStatusItem {
     SelfTestRamFailDfFail,
 }
 
+impl FailStatusItem {
+    /// Returns the index of the fail status byte (0: `FailStatus1`, 1: `FailStatus2`, 2: `FailStatus3`)
+    /// and the bit position the item is reported in
+    pub(crate) fn bit_position(&self) -> (usize, usize) {
+        use FailStatusItem::*;
+        match *self {
+            OverCurrentDischargeDetection65A => {
+                (0, FAIL_STATUS_1_OVER_CURRENT_DISCHARGE_DETECTION_65A_BIT)
+            }
+            OverCurrentDischargeDetection90A => {
+                (0, FAIL_STATUS_1_OVER_CURRENT_DISCHARGE_DETECTION_90A_BIT)
+            }
+            OverChargeProtection => (0, FAIL_STATUS_1_OVER_CHARGE_PROTECTION_BIT),
+            OverCurrentChargeDetection45A => {
+                (0, FAIL_STATUS_1_OVER_CURRENT_CHARGE_DETECTION_45A_BIT)
+            }
+            OverTemperatureDischargeDetection => {
+                (0, FAIL_STATUS_1_OVER_TEMPERATURE_DISCHARGE_DETECTION_BIT)
+            }
+            LowVoltageDetection => (0, FAIL_STATUS_1_LOW_VOLTAGE_DETECTION_BIT),
+            FullyChargeDetection => (0, FAIL_STATUS_1_FULLY_CHARGE_DETECTION_BIT),
+            OverCurrentDischargeDetection200A => {
+                (0, FAIL_STATUS_1_OVER_CURRENT_DISCHARGE_DETECTION_200A_BIT)
+            }
+            OverCurrentDischargeDetection110A => {
+                (1, FAIL_STATUS_2_OVER_CURRENT_DISCHARGE_DETECTION_110A_BIT)
+            }
+            OverCurrentChargeDetection65A => {
+                (1, FAIL_STATUS_2_OVER_CURRENT_CHARGE_DETECTION_65A_BIT)
+            }
+            OverTemperatureChargeDetection => {
+                (1, FAIL_STATUS_2_OVER_TEMPERATURE_CHARGE_DETECTION_BIT)
+            }
+            CellUnbalanceDetection => (1, FAIL_STATUS_2_CELL_UNBALANCE_DETECTION_BIT),
+            OverCharge => (1, FAIL_STATUS_2_OVER_CHARGE_BIT),
+            DeepDischarge => (1, FAIL_STATUS_2_DEEP_DISCHARGE_BIT),
+            FuseBlown => (1, FAIL_STATUS_2_FUSE_BLOWN_BIT),
+            FetUncontrol => (1, FAIL_STATUS_2_FET_UNCONTROL_BIT),
+            SelfTestClockFail => (2, FAIL_STATUS_3_SELF_TEST_CLOCK_FAIL_BIT),
+            SelfTestRomFail => (2, FAIL_STATUS_3_SELF_TEST_ROM_FAIL_BIT),
+            SelfTestRegisterFail => (2, FAIL_STATUS_3_SELF_TEST_REGISTER_FAIL_BIT),
+            SelfTestPswRegisterFail => (2, FAIL_STATUS_3_SELF_TEST_PSW_REGISTER_FAIL_BIT),
+            SelfTestStackRegisterFail => (2, FAIL_STATUS_3_SELF_TEST_STACK_REGISTER_FAIL_BIT),
+            SelfTestCsRegisterFail => (2, FAIL_STATUS_3_SELF_TEST_CS_REGISTER_FAIL_BIT),
+            SelfTestEsRegisterFail => (2, FAIL_STATUS_3_SELF_TEST_ES_REGISTER_FAIL_BIT),
+            SelfTestRamFailDfFail => (2, FAIL_STATUS_3_SELF_TEST_RAM_FAIL_DF_FAIL_BIT),
+        }
+    }
+}
+
 pub struct FailStatusValuesIter<'a, S>
 where
     S: FailStatus + ?Sized,
@@ -238,9 +313,27 @@ where
 pub trait FailStatus {
     fn fail_status(&self, item: FailStatusItem) -> FailState;
 
-    fn fail_status_values(&self) -> FailStatusValuesIter<Self> {
+    /// Returns the state of `item`, keeping the reason why it is unknown
+    /// By default, `FailState::Unknown` is reported as `UnavailableReason::NotAvailable`.
+    fn fail_status_reading(&self, item: FailStatusItem) -> FailStatusReading {
+        match self.fail_status(item) {
+            FailState::Ok => FailStatusReading::Ok,
+            FailState::Ng => FailStatusReading::Ng,
+            FailState::Unknown => FailStatusReading::Unavailable(UnavailableReason::NotAvailable),
+        }
+    }
+
+    fn fail_status_values(&self) -> FailStatusValuesIter<'_, Self> {
         FailStatusValuesIter::new(self)
     }
+
+    /// Returns the items whose state is `FailState::Ng`
+    fn active_faults(&self) -> FaultSet {
+        self.fail_status_values()
+            .filter(|(_, state)| *state == FailState::Ng)
+            .map(|(item, _)| item)
+            .collect()
+    }
 }
 
 impl<B: BatteryState> FailStatus for B {
@@ -345,6 +438,30 @@ impl<B: BatteryState> FailStatus for B {
                 .unwrap_or_default(),
         }
     }
+
+    /// Errors which mean the source does not carry the fail status are reported as
+    /// `UnavailableReason::NotAvailable`, any other error as `UnavailableReason::DecodeError`.
+    fn fail_status_reading(&self, item: FailStatusItem) -> FailStatusReading {
+        let (index, bit) = item.bit_position();
+        let byte: Result<u8> = match index {
+            0 => self.fail_status_1().map(|status| status.0),
+            1 => self.fail_status_2().map(|status| status.0),
+            _ => self.fail_status_3().map(|status| status.0),
+        };
+        match byte {
+            Ok(byte) => FailStatusReading::from_bit(byte, bit),
+            Err(error) => FailStatusReading::Unavailable(UnavailableReason::from_error(&error)),
+        }
+    }
+
+    /// Fail statuses which cannot be read are treated as having no active fault.
+    fn active_faults(&self) -> FaultSet {
+        FaultSet::from_raw([
+            self.fail_status_1().map(|status| status.0).unwrap_or(0),
+            self.fail_status_2().map(|status| status.0).unwrap_or(0),
+            self.fail_status_3().map(|status| status.0).unwrap_or(0),
+        ])
+    }
 }
 
 #[cfg(test)]
@@ -1015,4 +1132,47 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_serde() {
+        use FailStatusItem::*;
+
+        assert_eq!(serde_json::to_string(&FailState::Ng).unwrap(), r#""ng""#);
+        assert_eq!(
+            serde_json::from_str::<FailState>(r#""unknown""#).unwrap(),
+            FailState::Unknown
+        );
+        assert_eq!(serde_json::to_string(&FailStatus1(0x40)).unwrap(), "64");
+        assert_eq!(
+            serde_json::from_str::<FailStatus3>("1").unwrap(),
+            FailStatus3(0x01)
+        );
+
+        for (item, name) in [
+            (
+                OverCurrentDischargeDetection65A,
+                "over_current_discharge_detection_65a",
+            ),
+            (
+                OverCurrentChargeDetection45A,
+                "over_current_charge_detection_45a",
+            ),
+            (
+                OverCurrentDischargeDetection200A,
+                "over_current_discharge_detection_200a",
+            ),
+            (FullyChargeDetection, "fully_charge_detection"),
+            (FetUncontrol, "fet_uncontrol"),
+            (SelfTestRamFailDfFail, "self_test_ram_fail_df_fail"),
+        ] {
+            assert_eq!(
+                serde_json::to_string(&item).unwrap(),
+                format!(r#""{name}""#)
+            );
+        }
+        for item in FailStatusItem::iter() {
+            let json = serde_json::to_string(&item).unwrap();
+            assert_eq!(serde_json::from_str::<FailStatusItem>(&json).unwrap(), item);
+        }
+    }
 }
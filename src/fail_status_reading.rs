@@ -0,0 +1,447 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battery_state::BatteryState,
+    error::{Error, Result},
+    fail_status::*,
+    fault_set::FaultSet,
+};
+
+/// Why the state of a `FailStatusItem` is unknown
+/// Serialized in snake case, e.g. `"decode_error"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnavailableReason {
+    /// The source does not carry the fail status, e.g. FailStatus3 in a `BmInformation` frame
+    NotAvailable,
+    /// The source carries the fail status, but it could not be read
+    DecodeError,
+    /// The last value read is older than the allowed age
+    Stale,
+}
+
+impl UnavailableReason {
+    pub(crate) fn from_error(error: &Error) -> Self {
+        match error {
+            Error::NoAppropriateData { .. }
+            | Error::FieldNotAvailable(_)
+            | Error::UnsupportedField(_)
+            | Error::UnsupportedCommand(_) => UnavailableReason::NotAvailable,
+            _ => UnavailableReason::DecodeError,
+        }
+    }
+}
+
+/// State of a `FailStatusItem`, keeping the reason why it is unknown
+/// Serialized in snake case, e.g. `"ng"` or `{"unavailable": "stale"}`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailStatusReading {
+    Ok,
+    Ng,
+    Unavailable(UnavailableReason),
+}
+
+impl FailStatusReading {
+    pub(crate) fn from_bit(byte: u8, bit: usize) -> Self {
+        if byte & (0x01 << bit) == 0 {
+            FailStatusReading::Ok
+        } else {
+            FailStatusReading::Ng
+        }
+    }
+
+    pub fn fail_state(&self) -> FailState {
+        match self {
+            FailStatusReading::Ok => FailState::Ok,
+            FailStatusReading::Ng => FailState::Ng,
+            FailStatusReading::Unavailable(_) => FailState::Unknown,
+        }
+    }
+
+    pub fn unavailable_reason(&self) -> Option<UnavailableReason> {
+        match self {
+            FailStatusReading::Unavailable(reason) => Some(*reason),
+            _ => None,
+        }
+    }
+}
+
+impl From<FailStatusReading> for FailState {
+    fn from(reading: FailStatusReading) -> Self {
+        reading.fail_state()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Slot {
+    Empty,
+    Value { byte: u8, at: SystemTime },
+    DecodeError,
+}
+
+/// Merges the fail status bytes of several sources into one picture
+///
+/// Sources only have to carry some of `FailStatus1`, `FailStatus2` and `FailStatus3`,
+/// e.g. a `Command::FailStatus1` frame and a `Command::BmInformation` frame.
+/// The latest value of each byte is kept. A byte which could not be decoded
+/// does not replace a value read before.
+///
+/// ```
+/// use std::time::SystemTime;
+/// use fortelion::{
+///     BatterySnapshot, FailStatus, FailStatus1, FailStatus3, FailStatusItem, FailStatusMerger,
+///     FailStatusReading, UnavailableReason,
+/// };
+///
+/// let mut merger = FailStatusMerger::new();
+/// let now = SystemTime::now();
+/// merger.update(&BatterySnapshot { fail_status_1: Some(FailStatus1(0)), ..Default::default() }, now);
+/// merger.update(&BatterySnapshot { fail_status_3: Some(FailStatus3(0x02)), ..Default::default() }, now);
+///
+/// let merged = merger.merged(now);
+/// assert_eq!(merged.fail_status_reading(FailStatusItem::SelfTestRomFail), FailStatusReading::Ng);
+/// assert_eq!(
+///     merged.fail_status_reading(FailStatusItem::FuseBlown),
+///     FailStatusReading::Unavailable(UnavailableReason::NotAvailable)
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct FailStatusMerger {
+    max_age: Option<Duration>,
+    slots: [Slot; 3],
+}
+
+impl Default for FailStatusMerger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FailStatusMerger {
+    pub fn new() -> Self {
+        Self {
+            max_age: None,
+            slots: [Slot::Empty; 3],
+        }
+    }
+
+    /// Reports bytes read more than `max_age` ago as `UnavailableReason::Stale`
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Takes over the fail status bytes `state` carries, read at `at`
+    pub fn update(&mut self, state: &(impl BatteryState + ?Sized), at: SystemTime) {
+        let bytes = [
+            state.fail_status_1().map(|status| status.0),
+            state.fail_status_2().map(|status| status.0),
+            state.fail_status_3().map(|status| status.0),
+        ];
+        for (slot, byte) in self.slots.iter_mut().zip(bytes) {
+            Self::update_slot(slot, byte, at);
+        }
+    }
+
+    fn update_slot(slot: &mut Slot, byte: Result<u8>, at: SystemTime) {
+        match byte {
+            Ok(byte) => {
+                let newer = match slot {
+                    Slot::Value { at: previous, .. } => at >= *previous,
+                    _ => true,
+                };
+                if newer {
+                    *slot = Slot::Value { byte, at };
+                }
+            }
+            Err(error) => {
+                if UnavailableReason::from_error(&error) == UnavailableReason::DecodeError
+                    && *slot == Slot::Empty
+                {
+                    *slot = Slot::DecodeError;
+                }
+            }
+        }
+    }
+
+    /// Returns the merged fail status as of `now`
+    pub fn merged(&self, now: SystemTime) -> MergedFailStatus {
+        let mut bytes = [Err(UnavailableReason::NotAvailable); 3];
+        for (byte, slot) in bytes.iter_mut().zip(&self.slots) {
+            *byte = match *slot {
+                Slot::Empty => Err(UnavailableReason::NotAvailable),
+                Slot::DecodeError => Err(UnavailableReason::DecodeError),
+                Slot::Value { byte, at } => {
+                    let age = now.duration_since(at).unwrap_or_default();
+                    match self.max_age {
+                        Some(max_age) if age > max_age => Err(UnavailableReason::Stale),
+                        _ => Ok(byte),
+                    }
+                }
+            };
+        }
+        MergedFailStatus { bytes }
+    }
+}
+
+/// Fail status merged by `FailStatusMerger`
+/// Serialized with a field for each fail status byte, e.g.
+/// `{"fail_status_1": {"value": 64}, "fail_status_2": {"unavailable": "stale"}, ...}`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    from = "SerializedMergedFailStatus",
+    into = "SerializedMergedFailStatus"
+)]
+pub struct MergedFailStatus {
+    bytes: [std::result::Result<u8, UnavailableReason>; 3],
+}
+
+impl MergedFailStatus {
+    pub fn fail_status_1(&self) -> std::result::Result<FailStatus1, UnavailableReason> {
+        self.bytes[0].map(FailStatus1)
+    }
+
+    pub fn fail_status_2(&self) -> std::result::Result<FailStatus2, UnavailableReason> {
+        self.bytes[1].map(FailStatus2)
+    }
+
+    pub fn fail_status_3(&self) -> std::result::Result<FailStatus3, UnavailableReason> {
+        self.bytes[2].map(FailStatus3)
+    }
+
+    /// Returns whether all three fail status bytes are available
+    pub fn is_complete(&self) -> bool {
+        self.bytes.iter().all(|byte| byte.is_ok())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SerializedByte {
+    Value(u8),
+    Unavailable(UnavailableReason),
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedMergedFailStatus {
+    fail_status_1: SerializedByte,
+    fail_status_2: SerializedByte,
+    fail_status_3: SerializedByte,
+}
+
+impl From<MergedFailStatus> for SerializedMergedFailStatus {
+    fn from(status: MergedFailStatus) -> Self {
+        let [fail_status_1, fail_status_2, fail_status_3] = status.bytes.map(|byte| match byte {
+            Ok(byte) => SerializedByte::Value(byte),
+            Err(reason) => SerializedByte::Unavailable(reason),
+        });
+        Self {
+            fail_status_1,
+            fail_status_2,
+            fail_status_3,
+        }
+    }
+}
+
+impl From<SerializedMergedFailStatus> for MergedFailStatus {
+    fn from(status: SerializedMergedFailStatus) -> Self {
+        let bytes = [
+            status.fail_status_1,
+            status.fail_status_2,
+            status.fail_status_3,
+        ]
+        .map(|byte| match byte {
+            SerializedByte::Value(byte) => Ok(byte),
+            SerializedByte::Unavailable(reason) => Err(reason),
+        });
+        Self { bytes }
+    }
+}
+
+impl FailStatus for MergedFailStatus {
+    fn fail_status(&self, item: FailStatusItem) -> FailState {
+        self.fail_status_reading(item).fail_state()
+    }
+
+    fn fail_status_reading(&self, item: FailStatusItem) -> FailStatusReading {
+        let (index, bit) = item.bit_position();
+        match self.bytes[index] {
+            Ok(byte) => FailStatusReading::from_bit(byte, bit),
+            Err(reason) => FailStatusReading::Unavailable(reason),
+        }
+    }
+
+    fn active_faults(&self) -> FaultSet {
+        FaultSet::from_raw(self.bytes.map(|byte| byte.unwrap_or(0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        battery_snapshot::BatterySnapshot,
+        uart::{Command, DataFrame, DataFrameView},
+    };
+    use FailStatusItem::*;
+
+    // fails to decode everything
+    struct Corrupt;
+
+    fn corrupt<T>() -> Result<T> {
+        Err(Error::InvalidUartDataFrame("Invalid checksum".to_owned()))
+    }
+
+    impl BatteryState for Corrupt {
+        fn cell_voltages(&self) -> Result<Vec<u32>> {
+            corrupt()
+        }
+        fn current(&self) -> Result<i32> {
+            corrupt()
+        }
+        fn temperature(&self) -> Result<f64> {
+            corrupt()
+        }
+        fn remaining_capacity(&self) -> Result<u32> {
+            corrupt()
+        }
+        fn full_charge_capacity(&self) -> Result<u32> {
+            corrupt()
+        }
+        fn design_capacity(&self) -> Result<u32> {
+            corrupt()
+        }
+        fn absolute_state_of_charge(&self) -> Result<u32> {
+            corrupt()
+        }
+        fn relative_state_of_charge(&self) -> Result<u32> {
+            corrupt()
+        }
+        fn state_of_health(&self) -> Result<u32> {
+            corrupt()
+        }
+        fn bm_voltage(&self) -> Result<u32> {
+            corrupt()
+        }
+        fn fail_status_1(&self) -> Result<FailStatus1> {
+            corrupt()
+        }
+        fn fail_status_2(&self) -> Result<FailStatus2> {
+            corrupt()
+        }
+        fn fail_status_3(&self) -> Result<FailStatus3> {
+            corrupt()
+        }
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_reading_from_data_frame_view() {
+        let state = BatterySnapshot {
+            cell_voltages: Some(vec![3300; 8]),
+            current: Some(0),
+            temperature: Some(25.0),
+            remaining_capacity: Some(5000),
+            full_charge_capacity: Some(10000),
+            design_capacity: Some(10000),
+            state_of_health: Some(100),
+            fail_status_1: Some(FailStatus1(0)),
+            fail_status_2: Some(FailStatus2(0b0100_0000)),
+            ..Default::default()
+        };
+        let data_frame = DataFrame::encode(Command::BmInformation, &state).unwrap();
+        let view = DataFrameView::try_new(&data_frame).unwrap();
+        assert_eq!(view.fail_status_reading(FuseBlown), FailStatusReading::Ng);
+        assert_eq!(view.fail_status_reading(OverCharge), FailStatusReading::Ok);
+        assert_eq!(
+            view.fail_status_reading(SelfTestRomFail),
+            FailStatusReading::Unavailable(UnavailableReason::NotAvailable)
+        );
+
+        assert_eq!(
+            Corrupt.fail_status_reading(FuseBlown),
+            FailStatusReading::Unavailable(UnavailableReason::DecodeError)
+        );
+        assert_eq!(Corrupt.fail_status(FuseBlown), FailState::Unknown);
+    }
+
+    #[test]
+    fn test_merger() {
+        let mut merger = FailStatusMerger::new().with_max_age(Duration::from_secs(10));
+        merger.update(
+            &BatterySnapshot {
+                fail_status_1: Some(FailStatus1(0b0010_0000)),
+                fail_status_2: Some(FailStatus2(0)),
+                ..Default::default()
+            },
+            at(0),
+        );
+        merger.update(
+            &BatterySnapshot {
+                fail_status_2: Some(FailStatus2(0b0100_0000)),
+                fail_status_3: Some(FailStatus3(0)),
+                ..Default::default()
+            },
+            at(8),
+        );
+
+        let merged = merger.merged(at(9));
+        assert!(merged.is_complete());
+        assert_eq!(
+            merged.active_faults(),
+            FaultSet::from([LowVoltageDetection, FuseBlown])
+        );
+
+        let merged = merger.merged(at(15));
+        assert!(!merged.is_complete());
+        assert_eq!(merged.fail_status_1(), Err(UnavailableReason::Stale));
+        assert_eq!(
+            merged.fail_status_reading(LowVoltageDetection),
+            FailStatusReading::Unavailable(UnavailableReason::Stale)
+        );
+        assert_eq!(merged.fail_status_reading(FuseBlown), FailStatusReading::Ng);
+
+        let json = serde_json::to_string(&merged).unwrap();
+        assert_eq!(
+            json,
+            r#"{"fail_status_1":{"unavailable":"stale"},"fail_status_2":{"value":64},"fail_status_3":{"value":0}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<MergedFailStatus>(&json).unwrap(),
+            merged
+        );
+    }
+
+    #[test]
+    fn test_merger_decode_error() {
+        let mut merger = FailStatusMerger::new();
+        merger.update(
+            &BatterySnapshot {
+                fail_status_1: Some(FailStatus1(0)),
+                ..Default::default()
+            },
+            at(0),
+        );
+        merger.update(&Corrupt, at(0));
+        let merged = merger.merged(at(0));
+        assert_eq!(merged.fail_status_1(), Ok(FailStatus1(0)));
+        assert_eq!(merged.fail_status_2(), Err(UnavailableReason::DecodeError));
+
+        // a value read before is kept
+        merger.update(
+            &BatterySnapshot {
+                fail_status_2: Some(FailStatus2(0)),
+                ..Default::default()
+            },
+            at(1),
+        );
+        merger.update(&Corrupt, at(2));
+        assert_eq!(merger.merged(at(2)).fail_status_2(), Ok(FailStatus2(0)));
+    }
+}
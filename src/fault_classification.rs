@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
+
+use crate::{fail_status::FailStatusItem, fault_set::FaultSet};
+
+/// How serious an active `FailStatusItem` is, from least to most serious
+/// Serialized in snake case, e.g. `"protection_trip"`
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Display,
+    EnumIter,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultSeverity {
+    /// Normal status, e.g. fully charged
+    Informational,
+    /// Needs attention, but the module keeps working
+    Warning,
+    /// A protection has tripped; recovers once the cause is removed
+    ProtectionTrip,
+    /// The module is permanently damaged and must not be used any more
+    PermanentFailure,
+}
+
+impl FaultSeverity {
+    /// Returns whether the module can recover from the fault without being replaced
+    pub fn is_recoverable(&self) -> bool {
+        *self != FaultSeverity::PermanentFailure
+    }
+}
+
+/// What a `FailStatusItem` is about
+/// Serialized in snake case, e.g. `"self_test"`
+#[derive(Clone, Copy, Debug, Display, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultCategory {
+    Current,
+    Temperature,
+    Voltage,
+    SelfTest,
+    Hardware,
+}
+
+/// What an operator should do about an active `FailStatusItem`
+/// Serialized in snake case, e.g. `"reduce_discharge_current"`
+#[derive(Clone, Copy, Debug, Display, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecommendedAction {
+    /// Nothing to do
+    None,
+    /// Lower the load drawn from the module
+    ReduceDischargeCurrent,
+    /// Lower the charging current
+    ReduceChargeCurrent,
+    /// Stop charging the module
+    StopCharging,
+    /// Stop operating until the module has cooled down
+    CoolDown,
+    /// Charge the module
+    Recharge,
+    /// Charge the module fully so that its cells are balanced
+    BalanceCells,
+    /// Take the module out of service and replace it
+    ReplaceModule,
+}
+
+impl FailStatusItem {
+    pub fn severity(&self) -> FaultSeverity {
+        use FailStatusItem::*;
+        match *self {
+            FullyChargeDetection => FaultSeverity::Informational,
+            LowVoltageDetection | CellUnbalanceDetection => FaultSeverity::Warning,
+            OverCurrentDischargeDetection65A
+            | OverCurrentDischargeDetection90A
+            | OverCurrentDischargeDetection110A
+            | OverCurrentDischargeDetection200A
+            | OverCurrentChargeDetection45A
+            | OverCurrentChargeDetection65A
+            | OverChargeProtection
+            | OverTemperatureDischargeDetection
+            | OverTemperatureChargeDetection => FaultSeverity::ProtectionTrip,
+            OverCharge
+            | DeepDischarge
+            | FuseBlown
+            | FetUncontrol
+            | SelfTestClockFail
+            | SelfTestRomFail
+            | SelfTestRegisterFail
+            | SelfTestPswRegisterFail
+            | SelfTestStackRegisterFail
+            | SelfTestCsRegisterFail
+            | SelfTestEsRegisterFail
+            | SelfTestRamFailDfFail => FaultSeverity::PermanentFailure,
+        }
+    }
+
+    pub fn category(&self) -> FaultCategory {
+        use FailStatusItem::*;
+        match *self {
+            OverCurrentDischargeDetection65A
+            | OverCurrentDischargeDetection90A
+            | OverCurrentDischargeDetection110A
+            | OverCurrentDischargeDetection200A
+            | OverCurrentChargeDetection45A
+            | OverCurrentChargeDetection65A => FaultCategory::Current,
+            OverTemperatureDischargeDetection | OverTemperatureChargeDetection => {
+                FaultCategory::Temperature
+            }
+            OverChargeProtection
+            | LowVoltageDetection
+            | FullyChargeDetection
+            | CellUnbalanceDetection
+            | OverCharge
+            | DeepDischarge => FaultCategory::Voltage,
+            FuseBlown | FetUncontrol => FaultCategory::Hardware,
+            SelfTestClockFail
+            | SelfTestRomFail
+            | SelfTestRegisterFail
+            | SelfTestPswRegisterFail
+            | SelfTestStackRegisterFail
+            | SelfTestCsRegisterFail
+            | SelfTestEsRegisterFail
+            | SelfTestRamFailDfFail => FaultCategory::SelfTest,
+        }
+    }
+
+    pub fn recommended_action(&self) -> RecommendedAction {
+        use FailStatusItem::*;
+        if self.severity() == FaultSeverity::PermanentFailure {
+            return RecommendedAction::ReplaceModule;
+        }
+        match *self {
+            OverCurrentDischargeDetection65A
+            | OverCurrentDischargeDetection90A
+            | OverCurrentDischargeDetection110A
+            | OverCurrentDischargeDetection200A => RecommendedAction::ReduceDischargeCurrent,
+            OverCurrentChargeDetection45A | OverCurrentChargeDetection65A => {
+                RecommendedAction::ReduceChargeCurrent
+            }
+            OverChargeProtection => RecommendedAction::StopCharging,
+            OverTemperatureDischargeDetection | OverTemperatureChargeDetection => {
+                RecommendedAction::CoolDown
+            }
+            LowVoltageDetection => RecommendedAction::Recharge,
+            CellUnbalanceDetection => RecommendedAction::BalanceCells,
+            _ => RecommendedAction::None,
+        }
+    }
+}
+
+impl FaultSet {
+    /// Returns the highest severity among the items, or `None` if the set is empty
+    pub fn max_severity(&self) -> Option<FaultSeverity> {
+        self.iter().map(|item| item.severity()).max()
+    }
+
+    /// Returns the items whose severity is `severity` or higher
+    pub fn at_least(&self, severity: FaultSeverity) -> FaultSet {
+        self.iter()
+            .filter(|item| item.severity() >= severity)
+            .collect()
+    }
+
+    /// Returns the items in `category`
+    pub fn in_category(&self, category: FaultCategory) -> FaultSet {
+        self.iter()
+            .filter(|item| item.category() == category)
+            .collect()
+    }
+
+    /// Returns the recommended actions for the items, without duplicates
+    /// Ordered by the severity of the item requiring them, most severe first.
+    pub fn recommended_actions(&self) -> Vec<RecommendedAction> {
+        let mut items: Vec<_> = self.iter().collect();
+        items.sort_by_key(|item| std::cmp::Reverse(item.severity()));
+        let mut actions = Vec::new();
+        for item in items {
+            let action = item.recommended_action();
+            if action != RecommendedAction::None && !actions.contains(&action) {
+                actions.push(action);
+            }
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use FailStatusItem::*;
+
+    #[test]
+    fn test_classification() {
+        assert_eq!(
+            FullyChargeDetection.severity(),
+            FaultSeverity::Informational
+        );
+        assert_eq!(
+            FullyChargeDetection.recommended_action(),
+            RecommendedAction::None
+        );
+        assert_eq!(
+            OverCurrentDischargeDetection90A.severity(),
+            FaultSeverity::ProtectionTrip
+        );
+        assert_eq!(
+            OverCurrentDischargeDetection90A.category(),
+            FaultCategory::Current
+        );
+        assert_eq!(FuseBlown.category(), FaultCategory::Hardware);
+        assert_eq!(SelfTestRomFail.category(), FaultCategory::SelfTest);
+        assert!(!SelfTestRomFail.severity().is_recoverable());
+
+        for item in FailStatusItem::iter() {
+            assert_eq!(
+                item.recommended_action() == RecommendedAction::ReplaceModule,
+                item.severity() == FaultSeverity::PermanentFailure,
+                "{item}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_fault_set() {
+        let set = FaultSet::from([
+            FullyChargeDetection,
+            OverTemperatureChargeDetection,
+            CellUnbalanceDetection,
+            FuseBlown,
+        ]);
+        assert_eq!(set.max_severity(), Some(FaultSeverity::PermanentFailure));
+        assert_eq!(FaultSet::empty().max_severity(), None);
+        assert_eq!(
+            set.at_least(FaultSeverity::ProtectionTrip),
+            FaultSet::from([OverTemperatureChargeDetection, FuseBlown])
+        );
+        assert_eq!(
+            set.in_category(FaultCategory::Voltage),
+            FaultSet::from([FullyChargeDetection, CellUnbalanceDetection])
+        );
+        assert_eq!(
+            set.recommended_actions(),
+            vec![
+                RecommendedAction::ReplaceModule,
+                RecommendedAction::CoolDown,
+                RecommendedAction::BalanceCells
+            ]
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumString};
+
+use crate::fail_status::FailStatusItem;
+
+/// Language of fault descriptions
+/// Displayed, parsed and serialized as the language code, e.g. `"ja"`
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Display,
+    EnumIter,
+    EnumString,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub enum Locale {
+    #[default]
+    #[strum(serialize = "en")]
+    #[serde(rename = "en")]
+    English,
+    #[strum(serialize = "ja")]
+    #[serde(rename = "ja")]
+    Japanese,
+}
+
+/// Text describing a `FailStatusItem` to operators
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct FaultDescription {
+    /// A few words, for lists and status bars
+    pub short: &'static str,
+    /// One or two sentences, for details and maintenance reports
+    pub long: &'static str,
+}
+
+impl FailStatusItem {
+    pub fn description(&self, locale: Locale) -> FaultDescription {
+        let (short, long) = match locale {
+            Locale::English => english(*self),
+            Locale::Japanese => japanese(*self),
+        };
+        FaultDescription { short, long }
+    }
+
+    pub fn short_description(&self, locale: Locale) -> &'static str {
+        self.description(locale).short
+    }
+
+    pub fn long_description(&self, locale: Locale) -> &'static str {
+        self.description(locale).long
+    }
+}
+
+fn english(item: FailStatusItem) -> (&'static str, &'static str) {
+    use FailStatusItem::*;
+    match item {
+        OverCurrentDischargeDetection65A => (
+            "Discharge over current (65 A)",
+            "The discharge current exceeded 65 A and the discharge protection tripped.",
+        ),
+        OverCurrentDischargeDetection90A => (
+            "Discharge over current (90 A)",
+            "The discharge current exceeded 90 A and the discharge protection tripped.",
+        ),
+        OverChargeProtection => (
+            "Over charge protection",
+            "A cell voltage exceeded the over charge threshold and charging was stopped.",
+        ),
+        OverCurrentChargeDetection45A => (
+            "Charge over current (45 A)",
+            "The charge current exceeded 45 A and the charge protection tripped.",
+        ),
+        OverTemperatureDischargeDetection => (
+            "Discharge over temperature",
+            "The module temperature exceeded the limit for discharging and discharging was stopped.",
+        ),
+        LowVoltageDetection => (
+            "Low voltage",
+            "A cell voltage fell below the low voltage threshold. Recharge the module.",
+        ),
+        FullyChargeDetection => (
+            "Fully charged",
+            "The module is fully charged.",
+        ),
+        OverCurrentDischargeDetection200A => (
+            "Discharge over current (200 A)",
+            "The discharge current exceeded 200 A, e.g. by a short circuit, and the discharge protection tripped.",
+        ),
+        OverCurrentDischargeDetection110A => (
+            "Discharge over current (110 A)",
+            "The discharge current exceeded 110 A and the discharge protection tripped.",
+        ),
+        OverCurrentChargeDetection65A => (
+            "Charge over current (65 A)",
+            "The charge current exceeded 65 A and the charge protection tripped.",
+        ),
+        OverTemperatureChargeDetection => (
+            "Charge over temperature",
+            "The module temperature exceeded the limit for charging and charging was stopped.",
+        ),
+        CellUnbalanceDetection => (
+            "Cell unbalance",
+            "The voltage difference between cells is too large. Charge the module fully to balance the cells.",
+        ),
+        OverCharge => (
+            "Over charge failure",
+            "A cell was charged beyond its permanent failure threshold. Replace the module.",
+        ),
+        DeepDischarge => (
+            "Deep discharge failure",
+            "A cell was discharged below its permanent failure threshold. Replace the module.",
+        ),
+        FuseBlown => (
+            "Fuse blown",
+            "The protection fuse has blown. Replace the module.",
+        ),
+        FetUncontrol => (
+            "FET uncontrollable",
+            "The charge or discharge FET cannot be controlled. Replace the module.",
+        ),
+        SelfTestClockFail => (
+            "Self test: clock failure",
+            "The self test detected a clock failure. Replace the module.",
+        ),
+        SelfTestRomFail => (
+            "Self test: ROM failure",
+            "The self test detected a ROM failure. Replace the module.",
+        ),
+        SelfTestRegisterFail => (
+            "Self test: register failure",
+            "The self test detected a register failure. Replace the module.",
+        ),
+        SelfTestPswRegisterFail => (
+            "Self test: PSW register failure",
+            "The self test detected a PSW register failure. Replace the module.",
+        ),
+        SelfTestStackRegisterFail => (
+            "Self test: stack register failure",
+            "The self test detected a stack register failure. Replace the module.",
+        ),
+        SelfTestCsRegisterFail => (
+            "Self test: CS register failure",
+            "The self test detected a CS register failure. Replace the module.",
+        ),
+        SelfTestEsRegisterFail => (
+            "Self test: ES register failure",
+            "The self test detected an ES register failure. Replace the module.",
+        ),
+        SelfTestRamFailDfFail => (
+            "Self test: RAM / data flash failure",
+            "The self test detected a RAM or data flash failure. Replace the module.",
+        ),
+    }
+}
+
+fn japanese(item: FailStatusItem) -> (&'static str, &'static str) {
+    use FailStatusItem::*;
+    match item {
+        OverCurrentDischargeDetection65A => (
+            "放電過電流検出 (65 A)",
+            "放電電流が 65 A を超えたため、放電保護が動作しました。",
+        ),
+        OverCurrentDischargeDetection90A => (
+            "放電過電流検出 (90 A)",
+            "放電電流が 90 A を超えたため、放電保護が動作しました。",
+        ),
+        OverChargeProtection => (
+            "過充電保護",
+            "セル電圧が過充電しきい値を超えたため、充電を停止しました。",
+        ),
+        OverCurrentChargeDetection45A => (
+            "充電過電流検出 (45 A)",
+            "充電電流が 45 A を超えたため、充電保護が動作しました。",
+        ),
+        OverTemperatureDischargeDetection => (
+            "放電高温検出",
+            "モジュール温度が放電時の上限を超えたため、放電を停止しました。",
+        ),
+        LowVoltageDetection => (
+            "低電圧検出",
+            "セル電圧が低電圧しきい値を下回りました。モジュールを充電してください。",
+        ),
+        FullyChargeDetection => (
+            "満充電検出",
+            "モジュールは満充電です。",
+        ),
+        OverCurrentDischargeDetection200A => (
+            "放電過電流検出 (200 A)",
+            "短絡などにより放電電流が 200 A を超えたため、放電保護が動作しました。",
+        ),
+        OverCurrentDischargeDetection110A => (
+            "放電過電流検出 (110 A)",
+            "放電電流が 110 A を超えたため、放電保護が動作しました。",
+        ),
+        OverCurrentChargeDetection65A => (
+            "充電過電流検出 (65 A)",
+            "充電電流が 65 A を超えたため、充電保護が動作しました。",
+        ),
+        OverTemperatureChargeDetection => (
+            "充電高温検出",
+            "モジュール温度が充電時の上限を超えたため、充電を停止しました。",
+        ),
+        CellUnbalanceDetection => (
+            "セルアンバランス検出",
+            "セル間の電圧差が大きくなっています。満充電してセルバランスを取ってください。",
+        ),
+        OverCharge => (
+            "過充電異常",
+            "セルが永久故障しきい値を超えて充電されました。モジュールを交換してください。",
+        ),
+        DeepDischarge => (
+            "過放電異常",
+            "セルが永久故障しきい値を下回って放電されました。モジュールを交換してください。",
+        ),
+        FuseBlown => (
+            "ヒューズ溶断",
+            "保護ヒューズが溶断しました。モジュールを交換してください。",
+        ),
+        FetUncontrol => (
+            "FET 制御異常",
+            "充電または放電 FET を制御できません。モジュールを交換してください。",
+        ),
+        SelfTestClockFail => (
+            "自己診断: クロック異常",
+            "自己診断でクロック異常を検出しました。モジュールを交換してください。",
+        ),
+        SelfTestRomFail => (
+            "自己診断: ROM 異常",
+            "自己診断で ROM 異常を検出しました。モジュールを交換してください。",
+        ),
+        SelfTestRegisterFail => (
+            "自己診断: レジスタ異常",
+            "自己診断でレジスタ異常を検出しました。モジュールを交換してください。",
+        ),
+        SelfTestPswRegisterFail => (
+            "自己診断: PSW レジスタ異常",
+            "自己診断で PSW レジスタ異常を検出しました。モジュールを交換してください。",
+        ),
+        SelfTestStackRegisterFail => (
+            "自己診断: スタックレジスタ異常",
+            "自己診断でスタックレジスタ異常を検出しました。モジュールを交換してください。",
+        ),
+        SelfTestCsRegisterFail => (
+            "自己診断: CS レジスタ異常",
+            "自己診断で CS レジスタ異常を検出しました。モジュールを交換してください。",
+        ),
+        SelfTestEsRegisterFail => (
+            "自己診断: ES レジスタ異常",
+            "自己診断で ES レジスタ異常を検出しました。モジュールを交換してください。",
+        ),
+        SelfTestRamFailDfFail => (
+            "自己診断: RAM / データフラッシュ異常",
+            "自己診断で RAM またはデータフラッシュの異常を検出しました。モジュールを交換してください。",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, str::FromStr};
+
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    #[test]
+    fn test_catalog_is_complete_and_unique() {
+        for locale in Locale::iter() {
+            let shorts: HashSet<_> = FailStatusItem::iter()
+                .map(|item| item.short_description(locale))
+                .collect();
+            assert_eq!(shorts.len(), FailStatusItem::iter().count(), "{locale}");
+        }
+    }
+
+    #[test]
+    fn test_thresholds() {
+        use FailStatusItem::*;
+        for (item, threshold) in [
+            (OverCurrentDischargeDetection65A, "65 A"),
+            (OverCurrentDischargeDetection90A, "90 A"),
+            (OverCurrentDischargeDetection110A, "110 A"),
+            (OverCurrentDischargeDetection200A, "200 A"),
+            (OverCurrentChargeDetection45A, "45 A"),
+            (OverCurrentChargeDetection65A, "65 A"),
+        ] {
+            for locale in Locale::iter() {
+                let description = item.description(locale);
+                assert!(description.short.contains(threshold), "{item} {locale}");
+                assert!(description.long.contains(threshold), "{item} {locale}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_locale() {
+        assert_eq!(Locale::default(), Locale::English);
+        assert_eq!(Locale::from_str("ja").unwrap(), Locale::Japanese);
+        assert_eq!(Locale::Japanese.to_string(), "ja");
+        assert_eq!(
+            FailStatusItem::FuseBlown.short_description(Locale::Japanese),
+            "ヒューズ溶断"
+        );
+    }
+}
@@ -0,0 +1,400 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battery_module::{BatteryField, BatteryModule},
+    battery_snapshot::BatterySnapshot,
+    error::Result,
+    fail_status::*,
+    fault_set::FaultSet,
+    uart::Transport,
+};
+
+const DATA_FRAME_START_CODE: u8 = 0x02;
+const BM_ID_INDEX: usize = 1;
+
+/// Faults on the UART link, applied to frames being sent
+/// Serialized in snake case, e.g. `"duplicate"` or `{"truncate": 4}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkFault {
+    /// Drops the given number of bytes at random positions
+    DropBytes(usize),
+    /// Flips the bits of the checksum
+    CorruptChecksum,
+    /// Replaces the BM ID, keeping the checksum valid
+    WrongBmId(u8),
+    /// Keeps only the given number of leading bytes
+    Truncate(usize),
+    /// Waits before sending
+    Delay(Duration),
+    /// Sends the frame twice
+    Duplicate,
+}
+
+/// When a fault is injected
+/// Events are frames sent by `FaultyTransport` or reads of `FaultyModule`, counted from zero.
+/// Serialized in snake case, e.g. `"always"` or `{"probability": 0.1}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    Always,
+    /// Injected into each event with the given probability (0.0 - 1.0)
+    Probability(f64),
+    /// Injected into events in `from..until`
+    Events {
+        from: usize,
+        until: usize,
+    },
+    /// Injected between `from` and `until` after the script was created
+    Timeline {
+        from: Duration,
+        until: Duration,
+    },
+}
+
+/// xorshift64*, enough to make fault injection reproducible with a seed
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Faults paired with the triggers that inject them
+#[derive(Clone, Debug)]
+pub struct FaultScript<F> {
+    entries: Vec<(F, Trigger)>,
+    rng: Rng,
+    started: Instant,
+    events: usize,
+}
+
+impl<F: Clone> FaultScript<F> {
+    pub fn new() -> Self {
+        Self::with_seed(0x5eed)
+    }
+
+    /// Creates a script whose random decisions are reproducible
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            entries: vec![],
+            rng: Rng(seed.max(1)),
+            started: Instant::now(),
+            events: 0,
+        }
+    }
+
+    pub fn add(mut self, fault: F, trigger: Trigger) -> Self {
+        self.entries.push((fault, trigger));
+        self
+    }
+
+    /// Returns the faults to inject into the next event
+    pub fn next_faults(&mut self) -> Vec<F> {
+        let event = self.events;
+        self.events += 1;
+        let elapsed = self.started.elapsed();
+        let mut faults = vec![];
+        for (fault, trigger) in &self.entries {
+            let triggered = match trigger {
+                Trigger::Always => true,
+                Trigger::Probability(probability) => self.rng.next_f64() < *probability,
+                Trigger::Events { from, until } => (*from..*until).contains(&event),
+                Trigger::Timeline { from, until } => (*from..*until).contains(&elapsed),
+            };
+            if triggered {
+                faults.push(fault.clone());
+            }
+        }
+        faults
+    }
+}
+
+impl<F: Clone> Default for FaultScript<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `Transport` which injects link faults into the frames it sends
+///
+/// Wrap the device side (e.g. the transport of a `Responder`) to corrupt responses.
+pub struct FaultyTransport<T> {
+    inner: T,
+    script: FaultScript<LinkFault>,
+}
+
+impl<T: Transport> FaultyTransport<T> {
+    pub fn new(inner: T, script: FaultScript<LinkFault>) -> Self {
+        Self { inner, script }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+fn checksum_index(frame: &[u8]) -> Option<usize> {
+    // data frames end with a reserved byte after the checksum
+    match frame.first() {
+        Some(&DATA_FRAME_START_CODE) => frame.len().checked_sub(2),
+        _ => frame.len().checked_sub(1),
+    }
+}
+
+impl<T: Transport> Transport for FaultyTransport<T> {
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut frame = bytes.to_vec();
+        let mut copies = 1;
+        for fault in self.script.next_faults() {
+            match fault {
+                LinkFault::DropBytes(count) => {
+                    for _ in 0..count.min(frame.len()) {
+                        let index = self.script.rng.below(frame.len());
+                        frame.remove(index);
+                    }
+                }
+                LinkFault::CorruptChecksum => {
+                    if let Some(index) = checksum_index(&frame) {
+                        frame[index] ^= 0xff;
+                    }
+                }
+                LinkFault::WrongBmId(bm_id) => {
+                    if let (Some(index), true) = (checksum_index(&frame), frame.len() > BM_ID_INDEX)
+                    {
+                        // XOR checksum stays valid when the difference is applied to both
+                        frame[index] ^= frame[BM_ID_INDEX] ^ bm_id;
+                        frame[BM_ID_INDEX] = bm_id;
+                    }
+                }
+                LinkFault::Truncate(len) => frame.truncate(len),
+                LinkFault::Delay(delay) => std::thread::sleep(delay),
+                LinkFault::Duplicate => copies += 1,
+            }
+        }
+        for _ in 0..copies {
+            self.inner.send(&frame)?;
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.inner.receive(buf)
+    }
+}
+
+/// `BatteryModule` which raises fail status bits in what it reads
+pub struct FaultyModule<M> {
+    inner: M,
+    script: FaultScript<FailStatusItem>,
+}
+
+impl<M: BatteryModule> FaultyModule<M> {
+    pub fn new(inner: M, script: FaultScript<FailStatusItem>) -> Self {
+        Self { inner, script }
+    }
+
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut M {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> M {
+        self.inner
+    }
+}
+
+impl<M: BatteryModule> BatteryModule for FaultyModule<M> {
+    fn supported_fields(&self) -> Vec<BatteryField> {
+        self.inner.supported_fields()
+    }
+
+    fn read(&mut self, fields: &[BatteryField]) -> Result<BatterySnapshot> {
+        let mut snapshot = self.inner.read(fields)?;
+        let faults: FaultSet = self.script.next_faults().into_iter().collect();
+        let [fail_status_1, fail_status_2, fail_status_3] = faults.to_raw();
+        if let Some(status) = snapshot.fail_status_1.as_mut() {
+            status.0 |= fail_status_1;
+        }
+        if let Some(status) = snapshot.fail_status_2.as_mut() {
+            status.0 |= fail_status_2;
+        }
+        if let Some(status) = snapshot.fail_status_3.as_mut() {
+            status.0 |= fail_status_3;
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::Error,
+        uart::{Command, DataFrame},
+    };
+
+    #[derive(Default)]
+    struct Recorder(Vec<Vec<u8>>);
+
+    impl Transport for Recorder {
+        fn send(&mut self, bytes: &[u8]) -> Result<()> {
+            self.0.push(bytes.to_vec());
+            Ok(())
+        }
+
+        fn receive(&mut self, _buf: &mut [u8]) -> Result<()> {
+            Err(Error::UartFailedToReceive(
+                std::io::ErrorKind::TimedOut.into(),
+            ))
+        }
+    }
+
+    fn frame() -> Vec<u8> {
+        DataFrame::try_from_data(Command::Current, &[0x04, 0xd2])
+            .unwrap()
+            .as_ref()
+            .to_vec()
+    }
+
+    fn validate(bytes: &[u8]) -> Result<()> {
+        let mut data_frame = DataFrame::new(Command::Current);
+        if bytes.len() != data_frame.as_mut().len() {
+            return Err(Error::DataBytesShortage("length".to_owned()));
+        }
+        data_frame.as_mut().copy_from_slice(bytes);
+        data_frame.is_valid()
+    }
+
+    #[test]
+    fn test_link_faults() {
+        let script = FaultScript::new()
+            .add(
+                LinkFault::CorruptChecksum,
+                Trigger::Events { from: 0, until: 1 },
+            )
+            .add(
+                LinkFault::WrongBmId(0x03),
+                Trigger::Events { from: 1, until: 2 },
+            )
+            .add(
+                LinkFault::Truncate(3),
+                Trigger::Events { from: 2, until: 3 },
+            )
+            .add(LinkFault::Duplicate, Trigger::Events { from: 3, until: 4 })
+            .add(
+                LinkFault::DropBytes(2),
+                Trigger::Events { from: 4, until: 5 },
+            );
+        let mut transport = FaultyTransport::new(Recorder::default(), script);
+        for _ in 0..6 {
+            transport.send(&frame()).unwrap();
+        }
+        let sent = transport.into_inner().0;
+        assert_eq!(sent.len(), 7);
+
+        assert!(validate(&sent[0])
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid checksum"));
+        assert!(validate(&sent[1])
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid BM ID"));
+        assert_eq!(sent[2], frame()[..3]);
+        assert_eq!(sent[3], frame());
+        assert_eq!(sent[4], frame());
+        assert_eq!(sent[5].len(), frame().len() - 2);
+        assert!(validate(&sent[6]).is_ok());
+    }
+
+    #[test]
+    fn test_probability() {
+        let script =
+            FaultScript::with_seed(42).add(LinkFault::Duplicate, Trigger::Probability(0.3));
+        let mut transport = FaultyTransport::new(Recorder::default(), script);
+        for _ in 0..1000 {
+            transport.send(&frame()).unwrap();
+        }
+        let duplicated = transport.into_inner().0.len() - 1000;
+        assert!((200..400).contains(&duplicated), "{duplicated}");
+
+        let mut never = FaultScript::new().add(LinkFault::Duplicate, Trigger::Probability(0.0));
+        assert!((0..100).all(|_| never.next_faults().is_empty()));
+    }
+
+    #[test]
+    fn test_timeline() {
+        let mut script = FaultScript::new()
+            .add(
+                LinkFault::CorruptChecksum,
+                Trigger::Timeline {
+                    from: Duration::ZERO,
+                    until: Duration::from_secs(3600),
+                },
+            )
+            .add(
+                LinkFault::Duplicate,
+                Trigger::Timeline {
+                    from: Duration::from_secs(3600),
+                    until: Duration::from_secs(7200),
+                },
+            );
+        assert_eq!(script.next_faults(), vec![LinkFault::CorruptChecksum]);
+    }
+
+    #[test]
+    fn test_data_faults() {
+        let snapshot = BatterySnapshot {
+            fail_status_1: Some(FailStatus1(0x00)),
+            fail_status_2: Some(FailStatus2(0x00)),
+            ..Default::default()
+        };
+        let script = FaultScript::new()
+            .add(
+                FailStatusItem::FuseBlown,
+                Trigger::Events { from: 1, until: 2 },
+            )
+            .add(FailStatusItem::LowVoltageDetection, Trigger::Always)
+            .add(FailStatusItem::SelfTestRomFail, Trigger::Always);
+        let mut module = FaultyModule::new(snapshot, script);
+
+        let read = module.read_all().unwrap();
+        assert_eq!(read.fail_status_1, Some(FailStatus1(0b00100000)));
+        assert_eq!(read.fail_status_2, Some(FailStatus2(0x00)));
+        // not reported by the inner module, so it stays unavailable
+        assert_eq!(read.fail_status_3, None);
+
+        let read = module.read_all().unwrap();
+        assert_eq!(read.fail_status(FailStatusItem::FuseBlown), FailState::Ng);
+        assert_eq!(
+            read.fail_status(FailStatusItem::FetUncontrol),
+            FailState::Ok
+        );
+
+        let read = module.read_all().unwrap();
+        assert_eq!(read.fail_status(FailStatusItem::FuseBlown), FailState::Ok);
+    }
+}
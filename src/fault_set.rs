@@ -0,0 +1,370 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Sub, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+use crate::fail_status::*;
+
+// Items in order of their bit index in `FaultSet`
+const ITEMS: [FailStatusItem; 24] = {
+    use FailStatusItem::*;
+    [
+        OverCurrentDischargeDetection65A,
+        OverCurrentDischargeDetection90A,
+        OverChargeProtection,
+        OverCurrentChargeDetection45A,
+        OverTemperatureDischargeDetection,
+        LowVoltageDetection,
+        FullyChargeDetection,
+        OverCurrentDischargeDetection200A,
+        OverCurrentDischargeDetection110A,
+        OverCurrentChargeDetection65A,
+        OverTemperatureChargeDetection,
+        CellUnbalanceDetection,
+        OverCharge,
+        DeepDischarge,
+        FuseBlown,
+        FetUncontrol,
+        SelfTestClockFail,
+        SelfTestRomFail,
+        SelfTestRegisterFail,
+        SelfTestPswRegisterFail,
+        SelfTestStackRegisterFail,
+        SelfTestCsRegisterFail,
+        SelfTestEsRegisterFail,
+        SelfTestRamFailDfFail,
+    ]
+};
+
+fn mask(item: FailStatusItem) -> u32 {
+    let (byte, bit) = item.bit_position();
+    0x01 << (byte * 8 + bit)
+}
+
+/// Set of `FailStatusItem`s, e.g. the faults which are active
+///
+/// Holds the bits of `FailStatus1`, `FailStatus2` and `FailStatus3` together.
+/// Serialized as an array of `FailStatusItem`s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "Vec<FailStatusItem>", into = "Vec<FailStatusItem>")]
+pub struct FaultSet(u32);
+
+impl FaultSet {
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn all() -> Self {
+        Self(0x00ff_ffff)
+    }
+
+    /// Builds a set from raw fail status bytes (`[FailStatus1, FailStatus2, FailStatus3]`)
+    pub const fn from_raw(bytes: [u8; 3]) -> Self {
+        Self(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    }
+
+    /// Returns raw fail status bytes (`[FailStatus1, FailStatus2, FailStatus3]`)
+    pub const fn to_raw(&self) -> [u8; 3] {
+        let bytes = self.0.to_le_bytes();
+        [bytes[0], bytes[1], bytes[2]]
+    }
+
+    pub fn from_fail_statuses(
+        fail_status_1: FailStatus1,
+        fail_status_2: FailStatus2,
+        fail_status_3: FailStatus3,
+    ) -> Self {
+        Self::from_raw([fail_status_1.0, fail_status_2.0, fail_status_3.0])
+    }
+
+    pub fn to_fail_statuses(&self) -> (FailStatus1, FailStatus2, FailStatus3) {
+        let [fail_status_1, fail_status_2, fail_status_3] = self.to_raw();
+        (
+            FailStatus1(fail_status_1),
+            FailStatus2(fail_status_2),
+            FailStatus3(fail_status_3),
+        )
+    }
+
+    pub fn contains(&self, item: FailStatusItem) -> bool {
+        self.0 & mask(item) != 0
+    }
+
+    pub fn insert(&mut self, item: FailStatusItem) {
+        self.0 |= mask(item);
+    }
+
+    pub fn remove(&mut self, item: FailStatusItem) {
+        self.0 &= !mask(item);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn union(&self, other: FaultSet) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn intersection(&self, other: FaultSet) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Returns the items in `self` but not in `other`
+    pub fn difference(&self, other: FaultSet) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    pub fn is_subset(&self, other: FaultSet) -> bool {
+        self.difference(other).is_empty()
+    }
+
+    /// Iterates over the items in the set, in order of their bits
+    pub fn iter(&self) -> FaultSetIter {
+        FaultSetIter(self.0)
+    }
+}
+
+pub struct FaultSetIter(u32);
+
+impl Iterator for FaultSetIter {
+    type Item = FailStatusItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(ITEMS[index])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for FaultSetIter {}
+
+impl IntoIterator for FaultSet {
+    type Item = FailStatusItem;
+    type IntoIter = FaultSetIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &FaultSet {
+    type Item = FailStatusItem;
+    type IntoIter = FaultSetIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<FailStatusItem> for FaultSet {
+    fn from_iter<I: IntoIterator<Item = FailStatusItem>>(iter: I) -> Self {
+        let mut set = Self::empty();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<FailStatusItem> for FaultSet {
+    fn extend<I: IntoIterator<Item = FailStatusItem>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+impl From<FailStatusItem> for FaultSet {
+    fn from(item: FailStatusItem) -> Self {
+        Self(mask(item))
+    }
+}
+
+impl<const N: usize> From<[FailStatusItem; N]> for FaultSet {
+    fn from(items: [FailStatusItem; N]) -> Self {
+        items.into_iter().collect()
+    }
+}
+
+impl From<Vec<FailStatusItem>> for FaultSet {
+    fn from(items: Vec<FailStatusItem>) -> Self {
+        items.into_iter().collect()
+    }
+}
+
+impl From<FaultSet> for Vec<FailStatusItem> {
+    fn from(set: FaultSet) -> Self {
+        set.iter().collect()
+    }
+}
+
+impl BitOr for FaultSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl BitOrAssign for FaultSet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.union(rhs);
+    }
+}
+
+impl BitAnd for FaultSet {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        self.intersection(rhs)
+    }
+}
+
+impl BitAndAssign for FaultSet {
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self = self.intersection(rhs);
+    }
+}
+
+impl Sub for FaultSet {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self.difference(rhs)
+    }
+}
+
+impl SubAssign for FaultSet {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = self.difference(rhs);
+    }
+}
+
+impl FailStatus for FaultSet {
+    fn fail_status(&self, item: FailStatusItem) -> FailState {
+        if self.contains(item) {
+            FailState::Ng
+        } else {
+            FailState::Ok
+        }
+    }
+
+    fn active_faults(&self) -> FaultSet {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+    use FailStatusItem::*;
+
+    #[test]
+    fn test_items_order() {
+        for (index, item) in ITEMS.iter().enumerate() {
+            assert_eq!(mask(*item), 0x01 << index, "{item}");
+        }
+        assert_eq!(FailStatusItem::iter().count(), ITEMS.len());
+    }
+
+    #[test]
+    fn test_raw() {
+        let set = FaultSet::from_raw([0b0100_0001, 0b0100_0000, 0b0000_0010]);
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            vec![
+                OverCurrentDischargeDetection65A,
+                FullyChargeDetection,
+                FuseBlown,
+                SelfTestRomFail
+            ]
+        );
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.to_raw(), [0b0100_0001, 0b0100_0000, 0b0000_0010]);
+        assert_eq!(
+            set.to_fail_statuses(),
+            (
+                FailStatus1(0b0100_0001),
+                FailStatus2(0b0100_0000),
+                FailStatus3(0b0000_0010)
+            )
+        );
+        assert_eq!(FaultSet::all().to_raw(), [0xff; 3]);
+        assert_eq!(FaultSet::all().len(), 24);
+        assert!(FaultSet::empty().is_empty());
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let a = FaultSet::from([FuseBlown, LowVoltageDetection, SelfTestClockFail]);
+        let b = FaultSet::from([FuseBlown, FetUncontrol]);
+
+        assert!(a.contains(FuseBlown));
+        assert!(!a.contains(FetUncontrol));
+        assert_eq!(
+            a | b,
+            FaultSet::from([
+                FuseBlown,
+                LowVoltageDetection,
+                SelfTestClockFail,
+                FetUncontrol
+            ])
+        );
+        assert_eq!(a & b, FaultSet::from(FuseBlown));
+        assert_eq!(
+            a - b,
+            FaultSet::from([LowVoltageDetection, SelfTestClockFail])
+        );
+        assert!((a & b).is_subset(a));
+        assert!(!a.is_subset(b));
+
+        let mut c = a;
+        c.remove(FuseBlown);
+        c.insert(DeepDischarge);
+        assert_eq!(
+            c,
+            FaultSet::from([LowVoltageDetection, SelfTestClockFail, DeepDischarge])
+        );
+    }
+
+    #[test]
+    fn test_active_faults() {
+        let snapshot = BatterySnapshot {
+            fail_status_1: Some(FailStatus1(0b0010_0000)),
+            fail_status_2: Some(FailStatus2(0b1000_0000)),
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot.active_faults(),
+            FaultSet::from([LowVoltageDetection, FetUncontrol])
+        );
+
+        let set = FaultSet::from([OverCharge]);
+        assert_eq!(set.fail_status(OverCharge), FailState::Ng);
+        assert_eq!(set.fail_status(DeepDischarge), FailState::Ok);
+    }
+
+    #[test]
+    fn test_serde() {
+        let set = FaultSet::from([FuseBlown, OverCurrentChargeDetection65A]);
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(
+            json,
+            r#"["over_current_charge_detection_65a","fuse_blown"]"#
+        );
+        assert_eq!(serde_json::from_str::<FaultSet>(&json).unwrap(), set);
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{fail_status::*, fault_set::FaultSet};
+
+/// What happened to a `FailStatusItem`
+/// Serialized in snake case, e.g. `"raised"` or `{"cleared": {"active_for": ...}}`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultTransition {
+    Raised,
+    Cleared {
+        /// How long the fault was active, or `None` if it was not seen raised
+        active_for: Option<Duration>,
+    },
+    BecameUnknown,
+}
+
+/// A change of a `FailStatusItem` between successive readings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultEvent {
+    pub item: FailStatusItem,
+    pub transition: FaultTransition,
+    /// When the item was first read in its new state
+    pub at: SystemTime,
+}
+
+impl fmt::Display for FaultEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.transition {
+            FaultTransition::Raised => write!(f, "{} raised", self.item),
+            FaultTransition::Cleared {
+                active_for: Some(active_for),
+            } => write!(
+                f,
+                "{} cleared after {:.1} s",
+                self.item,
+                active_for.as_secs_f64()
+            ),
+            FaultTransition::Cleared { active_for: None } => write!(f, "{} cleared", self.item),
+            FaultTransition::BecameUnknown => write!(f, "{} became unknown", self.item),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct ItemState {
+    state: FailState,
+    raised_at: Option<SystemTime>,
+    // state differing from `state`, and when it was first read
+    pending: Option<(FailState, SystemTime)>,
+}
+
+/// Turns successive fail status readings into `FaultEvent`s
+///
+/// Every item starts as `FailState::Unknown`. A new state is only taken over
+/// once it has been read continuously for the debounce delay of that state,
+/// so faults which flicker shorter than the delay produce no event.
+/// Reading an item as `Ok` for the first time produces no event.
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use fortelion::{fault_tracker::*, FailStatusItem, FaultSet};
+///
+/// let mut tracker = FaultTracker::new();
+/// let t0 = SystemTime::UNIX_EPOCH;
+/// tracker.update(&FaultSet::from(FailStatusItem::FuseBlown), t0);
+/// let events = tracker.update(&FaultSet::empty(), t0 + Duration::from_millis(3200));
+/// assert_eq!(events[0].to_string(), "FuseBlown cleared after 3.2 s");
+/// ```
+#[derive(Clone, Debug)]
+pub struct FaultTracker {
+    raise_delay: Duration,
+    clear_delay: Duration,
+    items: HashMap<FailStatusItem, ItemState>,
+}
+
+impl Default for FaultTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FaultTracker {
+    pub fn new() -> Self {
+        Self {
+            raise_delay: Duration::ZERO,
+            clear_delay: Duration::ZERO,
+            items: FailStatusItem::iter()
+                .map(|item| (item, ItemState::default()))
+                .collect(),
+        }
+    }
+
+    /// Sets how long an item must be read as `Ng` before it is raised
+    pub fn with_raise_delay(mut self, delay: Duration) -> Self {
+        self.raise_delay = delay;
+        self
+    }
+
+    /// Sets how long an item must be read as `Ok` or `Unknown` before it is cleared or becomes unknown
+    pub fn with_clear_delay(mut self, delay: Duration) -> Self {
+        self.clear_delay = delay;
+        self
+    }
+
+    /// Feeds a reading taken at `at` and returns the resulting events, in order of `FailStatusItem`
+    pub fn update(
+        &mut self,
+        status: &(impl FailStatus + ?Sized),
+        at: SystemTime,
+    ) -> Vec<FaultEvent> {
+        let mut events = vec![];
+        for (item, read) in status.fail_status_values() {
+            let delay = if read == FailState::Ng {
+                self.raise_delay
+            } else {
+                self.clear_delay
+            };
+            let item_state = self.items.get_mut(&item).unwrap();
+            if read == item_state.state {
+                item_state.pending = None;
+                continue;
+            }
+            let since = match item_state.pending {
+                Some((pending, since)) if pending == read => since,
+                _ => {
+                    item_state.pending = Some((read, at));
+                    at
+                }
+            };
+            if at.duration_since(since).unwrap_or_default() < delay {
+                continue;
+            }
+
+            let transition = match read {
+                FailState::Ng => {
+                    item_state.raised_at = Some(since);
+                    Some(FaultTransition::Raised)
+                }
+                FailState::Ok => {
+                    let raised_at = item_state.raised_at.take();
+                    (item_state.state == FailState::Ng).then(|| FaultTransition::Cleared {
+                        active_for: raised_at
+                            .and_then(|raised_at| since.duration_since(raised_at).ok()),
+                    })
+                }
+                FailState::Unknown => {
+                    item_state.raised_at = None;
+                    Some(FaultTransition::BecameUnknown)
+                }
+            };
+            item_state.state = read;
+            item_state.pending = None;
+            if let Some(transition) = transition {
+                events.push(FaultEvent {
+                    item,
+                    transition,
+                    at: since,
+                });
+            }
+        }
+        events
+    }
+
+    /// Feeds a reading taken now
+    pub fn update_now(&mut self, status: &(impl FailStatus + ?Sized)) -> Vec<FaultEvent> {
+        self.update(status, SystemTime::now())
+    }
+
+    /// Returns the debounced state of `item`
+    pub fn state(&self, item: FailStatusItem) -> FailState {
+        self.items[&item].state
+    }
+
+    /// Returns the items currently raised
+    pub fn active_faults(&self) -> FaultSet {
+        self.items
+            .iter()
+            .filter(|(_, item_state)| item_state.state == FailState::Ng)
+            .map(|(item, _)| *item)
+            .collect()
+    }
+
+    /// Returns when `item` was raised, if it is active
+    pub fn active_since(&self, item: FailStatusItem) -> Option<SystemTime> {
+        self.items[&item].raised_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+    use FailStatusItem::*;
+
+    fn at(millis: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_raise_and_clear() {
+        let mut tracker = FaultTracker::new();
+        assert!(tracker.update(&FaultSet::empty(), at(0)).is_empty());
+
+        let events = tracker.update(&FaultSet::from([FuseBlown, OverCharge]), at(1000));
+        assert_eq!(
+            events,
+            vec![
+                FaultEvent {
+                    item: OverCharge,
+                    transition: FaultTransition::Raised,
+                    at: at(1000)
+                },
+                FaultEvent {
+                    item: FuseBlown,
+                    transition: FaultTransition::Raised,
+                    at: at(1000)
+                },
+            ]
+        );
+        assert_eq!(
+            tracker.active_faults(),
+            FaultSet::from([FuseBlown, OverCharge])
+        );
+        assert_eq!(tracker.active_since(FuseBlown), Some(at(1000)));
+
+        assert!(tracker
+            .update(&FaultSet::from([FuseBlown, OverCharge]), at(2000))
+            .is_empty());
+        let events = tracker.update(&FaultSet::from(FuseBlown), at(4200));
+        assert_eq!(
+            events,
+            vec![FaultEvent {
+                item: OverCharge,
+                transition: FaultTransition::Cleared {
+                    active_for: Some(Duration::from_millis(3200))
+                },
+                at: at(4200)
+            }]
+        );
+        assert_eq!(events[0].to_string(), "OverCharge cleared after 3.2 s");
+        assert_eq!(tracker.active_since(OverCharge), None);
+    }
+
+    #[test]
+    fn test_debounce() {
+        let mut tracker = FaultTracker::new()
+            .with_raise_delay(Duration::from_millis(500))
+            .with_clear_delay(Duration::from_millis(500));
+        tracker.update(&FaultSet::empty(), at(0));
+
+        // flicker
+        assert!(tracker
+            .update(&FaultSet::from(FuseBlown), at(100))
+            .is_empty());
+        assert!(tracker.update(&FaultSet::empty(), at(200)).is_empty());
+
+        assert!(tracker
+            .update(&FaultSet::from(FuseBlown), at(300))
+            .is_empty());
+        let events = tracker.update(&FaultSet::from(FuseBlown), at(800));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].transition, FaultTransition::Raised);
+        assert_eq!(events[0].at, at(300));
+
+        assert!(tracker.update(&FaultSet::empty(), at(900)).is_empty());
+        assert!(tracker
+            .update(&FaultSet::from(FuseBlown), at(1000))
+            .is_empty());
+        assert!(tracker.update(&FaultSet::empty(), at(1100)).is_empty());
+        let events = tracker.update(&FaultSet::empty(), at(1600));
+        assert_eq!(
+            events[0].transition,
+            FaultTransition::Cleared {
+                active_for: Some(Duration::from_millis(800))
+            }
+        );
+    }
+
+    #[test]
+    fn test_became_unknown() {
+        let mut tracker = FaultTracker::new();
+        let snapshot = BatterySnapshot {
+            fail_status_2: Some(FailStatus2(0b0100_0000)),
+            ..Default::default()
+        };
+        let events = tracker.update(&snapshot, at(0));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].item, FuseBlown);
+        assert_eq!(tracker.state(FuseBlown), FailState::Ng);
+        assert_eq!(tracker.state(SelfTestRomFail), FailState::Unknown);
+
+        // every item of FailStatus2 was known
+        let events = tracker.update(&BatterySnapshot::default(), at(1000));
+        assert_eq!(events.len(), 8);
+        assert!(events
+            .iter()
+            .all(|event| event.transition == FaultTransition::BecameUnknown));
+        assert!(tracker.active_faults().is_empty());
+    }
+}
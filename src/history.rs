@@ -0,0 +1,377 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    battery_module::BatteryModule, battery_snapshot::BatterySnapshot, error::Result,
+    fail_status::FailStatus, fault_set::FaultSet,
+};
+
+/// Shortest rollup interval
+const MIN_ROLLUP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A reading kept at raw resolution
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub at: SystemTime,
+    /// Fields which could be read
+    pub snapshot: BatterySnapshot,
+}
+
+/// Minimum, maximum and mean of a value over a rollup interval
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub count: u32,
+}
+
+impl Statistics {
+    fn new(value: f64) -> Self {
+        Self {
+            min: value,
+            max: value,
+            mean: value,
+            count: 1,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.mean += (value - self.mean) / self.count as f64;
+    }
+}
+
+fn accumulate(statistics: &mut Option<Statistics>, value: Option<f64>) {
+    if let Some(value) = value {
+        match statistics {
+            Some(statistics) => statistics.add(value),
+            None => *statistics = Some(Statistics::new(value)),
+        }
+    }
+}
+
+/// Readings summarized over one rollup interval
+/// Each value is `None` if it was never read during the interval.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rollup {
+    /// Start of the interval, aligned to a multiple of the interval since the Unix epoch
+    pub start: SystemTime,
+    pub duration: Duration,
+    pub samples: u32,
+    /// Unit: mA
+    pub current: Option<Statistics>,
+    /// Unit: mV
+    pub bm_voltage: Option<Statistics>,
+    /// Lowest cell voltage of each reading
+    /// Unit: mV
+    pub min_cell_voltage: Option<Statistics>,
+    /// Highest cell voltage of each reading
+    /// Unit: mV
+    pub max_cell_voltage: Option<Statistics>,
+    /// Unit: degC
+    pub temperature: Option<Statistics>,
+    /// Unit: %
+    pub relative_state_of_charge: Option<Statistics>,
+    /// Faults active in any reading of the interval
+    pub faults: FaultSet,
+}
+
+impl Rollup {
+    fn new(start: SystemTime, duration: Duration) -> Self {
+        Self {
+            start,
+            duration,
+            samples: 0,
+            current: None,
+            bm_voltage: None,
+            min_cell_voltage: None,
+            max_cell_voltage: None,
+            temperature: None,
+            relative_state_of_charge: None,
+            faults: FaultSet::empty(),
+        }
+    }
+
+    fn add(&mut self, snapshot: &BatterySnapshot) {
+        self.samples += 1;
+        accumulate(&mut self.current, snapshot.current.map(|v| v as f64));
+        accumulate(&mut self.bm_voltage, snapshot.bm_voltage.map(|v| v as f64));
+        let cell_voltages = snapshot.cell_voltages.as_deref().unwrap_or_default();
+        accumulate(
+            &mut self.min_cell_voltage,
+            cell_voltages.iter().min().map(|v| *v as f64),
+        );
+        accumulate(
+            &mut self.max_cell_voltage,
+            cell_voltages.iter().max().map(|v| *v as f64),
+        );
+        accumulate(&mut self.temperature, snapshot.temperature);
+        accumulate(
+            &mut self.relative_state_of_charge,
+            snapshot.relative_state_of_charge.map(|v| v as f64),
+        );
+        self.faults |= snapshot.active_faults();
+    }
+
+    pub fn end(&self) -> SystemTime {
+        self.start + self.duration
+    }
+}
+
+/// Bounded, time-indexed history of readings
+///
+/// Recent readings are kept at raw resolution for the raw retention. Every reading
+/// is also summarized into rollups of the rollup interval, which are kept for the
+/// rollup retention, so older data remains available at a coarser resolution.
+/// Retention is counted back from the latest reading.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TelemetryHistory {
+    raw_retention: Duration,
+    #[serde(deserialize_with = "deserialize_rollup_interval")]
+    rollup_interval: Duration,
+    rollup_retention: Duration,
+    records: VecDeque<Record>,
+    rollups: VecDeque<Rollup>,
+}
+
+impl Default for TelemetryHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelemetryHistory {
+    pub fn new() -> Self {
+        Self {
+            raw_retention: Duration::from_secs(10 * 60),
+            rollup_interval: Duration::from_secs(60),
+            rollup_retention: Duration::from_secs(24 * 60 * 60),
+            records: VecDeque::new(),
+            rollups: VecDeque::new(),
+        }
+    }
+
+    /// Sets how long readings are kept at raw resolution (default: 10 min)
+    pub fn with_raw_retention(mut self, retention: Duration) -> Self {
+        self.raw_retention = retention;
+        self
+    }
+
+    /// Sets the interval readings are summarized over (default: 60 s)
+    pub fn with_rollup_interval(mut self, interval: Duration) -> Self {
+        self.rollup_interval = interval.max(MIN_ROLLUP_INTERVAL);
+        self.rollups.clear();
+        self
+    }
+
+    /// Sets how long rollups are kept (default: 24 h)
+    pub fn with_rollup_retention(mut self, retention: Duration) -> Self {
+        self.rollup_retention = retention;
+        self
+    }
+
+    /// Reads all the supported fields of `module` at once and records them at `at`
+    /// Fields are kept at the resolution `module` reads them at. `UartBatteryModule` reads
+    /// the current, the temperature and the capacities through their own commands.
+    /// Nothing is recorded if the read fails.
+    pub fn record(
+        &mut self,
+        module: &mut (impl BatteryModule + ?Sized),
+        at: SystemTime,
+    ) -> Result<()> {
+        let snapshot = module.read_all()?;
+        self.insert(snapshot, at);
+        Ok(())
+    }
+
+    /// Records a snapshot read at `at`, e.g. the result of `BatteryModule::read`
+    /// Readings older than the latest one are put in order.
+    pub fn insert(&mut self, snapshot: BatterySnapshot, at: SystemTime) {
+        let start = self.rollup_start(at);
+        let index = self.rollups.partition_point(|rollup| rollup.start < start);
+        if self.rollups.get(index).map(|rollup| rollup.start) != Some(start) {
+            self.rollups
+                .insert(index, Rollup::new(start, self.rollup_interval));
+        }
+        self.rollups[index].add(&snapshot);
+
+        let index = self.records.partition_point(|record| record.at <= at);
+        self.records.insert(index, Record { at, snapshot });
+        self.prune();
+    }
+
+    fn rollup_start(&self, at: SystemTime) -> SystemTime {
+        let since_epoch = at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let interval = self.rollup_interval.as_nanos();
+        let start = since_epoch.as_nanos() / interval * interval;
+        SystemTime::UNIX_EPOCH
+            + Duration::new(
+                (start / 1_000_000_000) as u64,
+                (start % 1_000_000_000) as u32,
+            )
+    }
+
+    fn prune(&mut self) {
+        let Some(latest) = self.latest().map(|record| record.at) else {
+            return;
+        };
+        let expired = |retention: Duration, at: SystemTime| {
+            latest.duration_since(at).is_ok_and(|age| age > retention)
+        };
+        while self
+            .records
+            .front()
+            .is_some_and(|record| expired(self.raw_retention, record.at))
+        {
+            self.records.pop_front();
+        }
+        while self
+            .rollups
+            .front()
+            .is_some_and(|rollup| expired(self.rollup_retention, rollup.end()))
+        {
+            self.rollups.pop_front();
+        }
+    }
+
+    /// Returns the latest reading
+    pub fn latest(&self) -> Option<&Record> {
+        self.records.back()
+    }
+
+    /// Returns the raw readings taken from `from` until before `to`
+    pub fn range(&self, from: SystemTime, to: SystemTime) -> impl Iterator<Item = &Record> {
+        let start = self.records.partition_point(|record| record.at < from);
+        let end = self
+            .records
+            .partition_point(|record| record.at < to)
+            .max(start);
+        self.records.range(start..end)
+    }
+
+    /// Returns the rollups overlapping the time from `from` until before `to`
+    pub fn rollups(&self, from: SystemTime, to: SystemTime) -> impl Iterator<Item = &Rollup> {
+        let start = self.rollups.partition_point(|rollup| rollup.end() <= from);
+        let end = self
+            .rollups
+            .partition_point(|rollup| rollup.start < to)
+            .max(start);
+        self.rollups.range(start..end)
+    }
+
+    /// Returns the number of raw readings kept
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.rollups.clear();
+    }
+}
+
+// clamped like `with_rollup_interval`, so that a persisted history cannot divide by zero
+fn deserialize_rollup_interval<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Duration, D::Error> {
+    Ok(Duration::deserialize(deserializer)?.max(MIN_ROLLUP_INTERVAL))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fail_status::FailStatusItem, FailStatus1};
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn snapshot(current: i32) -> BatterySnapshot {
+        BatterySnapshot {
+            current: Some(current),
+            cell_voltages: Some(vec![3300, 3310, 3290]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        let mut history = TelemetryHistory::new();
+        for secs in 0..10 {
+            history
+                .record(&mut snapshot(secs as i32), at(secs))
+                .unwrap();
+        }
+        // out of order
+        history.insert(snapshot(100), at(4));
+        let currents: Vec<_> = history
+            .range(at(3), at(6))
+            .map(|record| record.snapshot.current.unwrap())
+            .collect();
+        assert_eq!(currents, vec![3, 4, 100, 5]);
+        assert_eq!(history.latest().unwrap().at, at(9));
+        assert_eq!(history.range(at(20), at(10)).count(), 0);
+    }
+
+    #[test]
+    fn test_rollups_and_retention() {
+        let mut history = TelemetryHistory::new()
+            .with_raw_retention(Duration::from_secs(60))
+            .with_rollup_interval(Duration::from_secs(60))
+            .with_rollup_retention(Duration::from_secs(600));
+        for secs in 0..1200 {
+            let mut state = snapshot(-((secs % 60) as i32));
+            if secs == 1150 {
+                state.fail_status_1 = Some(FailStatus1(0x40));
+            }
+            history.record(&mut state, at(secs)).unwrap();
+        }
+        // raw readings of the last 60 s
+        assert_eq!(history.len(), 61);
+        assert_eq!(history.range(at(0), at(1100)).count(), 0);
+
+        let rollups: Vec<_> = history.rollups(at(0), at(2000)).collect();
+        // rollups ending within the last 600 s
+        assert_eq!(rollups.len(), 11);
+        assert_eq!(rollups[0].start, at(540));
+        let last = rollups.last().unwrap();
+        assert_eq!(last.samples, 60);
+        let current = last.current.unwrap();
+        assert_eq!((current.min, current.max), (-59.0, 0.0));
+        assert!((current.mean + 29.5).abs() < 1e-9);
+        assert_eq!(last.min_cell_voltage.unwrap().max, 3290.0);
+        assert_eq!(last.temperature, None);
+        assert!(last.faults.contains(FailStatusItem::FullyChargeDetection));
+        assert!(rollups[0].faults.is_empty());
+
+        assert_eq!(history.rollups(at(1130), at(1131)).count(), 1);
+    }
+
+    #[test]
+    fn test_zero_rollup_interval() {
+        let json = serde_json::to_string(&TelemetryHistory::new())
+            .unwrap()
+            .replace(
+                r#""rollup_interval":{"secs":60"#,
+                r#""rollup_interval":{"secs":0"#,
+            );
+        assert!(json.contains(r#""rollup_interval":{"secs":0,"nanos":0}"#));
+        let mut history: TelemetryHistory = serde_json::from_str(&json).unwrap();
+        history.insert(snapshot(0), at(10));
+        let rollup = history.rollups(at(0), at(20)).next().unwrap();
+        assert_eq!(rollup.duration, Duration::from_secs(1));
+    }
+}
@@ -1,14 +1,40 @@
 // buggy: https://github.com/rust-lang/rust-clippy/issues?q=is%3Aissue+derive_partial_eq_without_eq
 #![allow(clippy::derive_partial_eq_without_eq)]
 
+pub mod alarm;
+pub mod analytics;
+mod battery_module;
+mod battery_snapshot;
 mod battery_state;
+pub mod charge_phase;
+pub mod derating;
 mod error;
 mod fail_status;
+mod fail_status_reading;
+mod fault_classification;
+mod fault_description;
+pub mod fault_injection;
+mod fault_set;
+pub mod fault_tracker;
+pub mod history;
+pub mod pack;
+pub mod plausibility;
+pub mod simulator;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod uart;
 mod utils;
 
+pub use battery_module::{BatteryField, BatteryModule};
+pub use battery_snapshot::BatterySnapshot;
 pub use battery_state::BatteryState;
 pub use error::{Error, Result};
 pub use fail_status::{
     FailState, FailStatus, FailStatus1, FailStatus2, FailStatus3, FailStatusItem,
 };
+pub use fail_status_reading::{
+    FailStatusMerger, FailStatusReading, MergedFailStatus, UnavailableReason,
+};
+pub use fault_classification::{FaultCategory, FaultSeverity, RecommendedAction};
+pub use fault_description::{FaultDescription, Locale};
+pub use fault_set::{FaultSet, FaultSetIter};
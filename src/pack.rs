@@ -0,0 +1,372 @@
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{
+    battery_module::BatteryField,
+    battery_state::BatteryState,
+    error::{Error, Result},
+    fail_status::*,
+};
+
+/// How the modules of a pack are connected
+/// Serialized in snake case, e.g. `"series"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    /// Voltages add up, the same current flows through every module
+    Series,
+    /// Currents and capacities add up, every module has the same voltage
+    Parallel,
+}
+
+/// How the state of charge and health of a parallel pack are derived from the modules
+/// A series pack is always as charged as its weakest module, which empties the string first.
+/// Serialized in snake case, e.g. `"capacity_weighted"`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateOfChargeMode {
+    /// Mean of the modules, weighted by their capacity
+    #[default]
+    CapacityWeighted,
+    /// The lowest of the modules
+    Weakest,
+}
+
+/// Several battery modules seen as one battery
+///
+/// `Pack` implements `BatteryState`, so it can be used wherever a single module is.
+/// A field can only be read if it can be read from every module.
+///
+/// - Cell voltages are those of every module, module by module.
+/// - The current is the mean of the modules in series, and their sum in parallel.
+/// - The voltage is the sum of the modules in series, and their mean in parallel.
+/// - Capacities are those of the smallest module in series, and their sum in parallel.
+/// - States of charge and health are those of the weakest module in series, and are derived
+///   according to the `StateOfChargeMode` in parallel.
+/// - The temperature is the highest of the modules.
+/// - Fail statuses are merged: a bit is set if it is set in any module.
+///
+/// ```
+/// use fortelion::{pack::*, BatterySnapshot, BatteryState};
+///
+/// let module = BatterySnapshot {
+///     bm_voltage: Some(26400),
+///     ..Default::default()
+/// };
+/// let pack = Pack::new(Topology::Series, vec![module.clone(), module]);
+/// assert_eq!(pack.bm_voltage().unwrap(), 52800);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Pack<S> {
+    topology: Topology,
+    state_of_charge_mode: StateOfChargeMode,
+    modules: Vec<S>,
+}
+
+impl<S: BatteryState> Pack<S> {
+    pub fn new(topology: Topology, modules: Vec<S>) -> Self {
+        Self {
+            topology,
+            state_of_charge_mode: StateOfChargeMode::default(),
+            modules,
+        }
+    }
+
+    /// Sets how the state of charge and health of a parallel pack are derived (default: `CapacityWeighted`)
+    pub fn with_state_of_charge_mode(mut self, mode: StateOfChargeMode) -> Self {
+        self.state_of_charge_mode = mode;
+        self
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn modules(&self) -> &[S] {
+        &self.modules
+    }
+
+    pub fn modules_mut(&mut self) -> &mut [S] {
+        &mut self.modules
+    }
+
+    /// Returns the indices of the modules in which `item` is set
+    pub fn fault_modules(&self, item: FailStatusItem) -> Vec<usize> {
+        self.modules
+            .iter()
+            .enumerate()
+            .filter(|(_, module)| module.fail_status(item) == FailState::Ng)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Returns every item set in any module, with the indices of the modules in which it is set
+    pub fn raised_faults(&self) -> Vec<(FailStatusItem, Vec<usize>)> {
+        FailStatusItem::iter()
+            .map(|item| (item, self.fault_modules(item)))
+            .filter(|(_, modules)| !modules.is_empty())
+            .collect()
+    }
+
+    fn read<T>(&self, field: BatteryField, read: impl Fn(&S) -> Result<T>) -> Result<Vec<T>> {
+        if self.modules.is_empty() {
+            return Err(Error::FieldNotAvailable(field));
+        }
+        self.modules.iter().map(read).collect()
+    }
+
+    fn capacity(&self, field: BatteryField, read: impl Fn(&S) -> Result<u32>) -> Result<u32> {
+        let capacities = self.read(field, read)?;
+        Ok(match self.topology {
+            Topology::Series => capacities.into_iter().min().unwrap_or_default(),
+            Topology::Parallel => capacities.into_iter().sum(),
+        })
+    }
+
+    /// Combines a percentage of each module, weighted by `weight` in a parallel pack
+    fn percentage(
+        &self,
+        field: BatteryField,
+        read: impl Fn(&S) -> Result<u32>,
+        weight: impl Fn(&S) -> Result<u32>,
+    ) -> Result<u32> {
+        let values = self.read(field, read)?;
+        if self.topology == Topology::Series
+            || self.state_of_charge_mode == StateOfChargeMode::Weakest
+        {
+            return Ok(values.into_iter().min().unwrap_or_default());
+        }
+        let weights = self.read(field, weight)?;
+        let total: f64 = weights.iter().map(|weight| *weight as f64).sum();
+        if total == 0.0 {
+            return Ok(values.into_iter().min().unwrap_or_default());
+        }
+        let weighted: f64 = values
+            .iter()
+            .zip(&weights)
+            .map(|(value, weight)| *value as f64 * *weight as f64)
+            .sum();
+        Ok((weighted / total).round() as u32)
+    }
+
+    fn merged<T: Copy>(
+        &self,
+        field: BatteryField,
+        read: impl Fn(&S) -> Result<T>,
+        byte: impl Fn(T) -> u8,
+    ) -> Result<u8> {
+        Ok(self
+            .read(field, read)?
+            .into_iter()
+            .fold(0, |merged, status| merged | byte(status)))
+    }
+}
+
+impl<S: BatteryState> BatteryState for Pack<S> {
+    /// Returns the cell voltages of every module, module by module
+    /// Unit: mV
+    fn cell_voltages(&self) -> Result<Vec<u32>> {
+        Ok(self
+            .read(BatteryField::CellVoltages, |module| module.cell_voltages())?
+            .concat())
+    }
+
+    /// Unit: mA
+    fn current(&self) -> Result<i32> {
+        let currents = self.read(BatteryField::Current, |module| module.current())?;
+        let sum: i64 = currents.iter().map(|current| *current as i64).sum();
+        Ok(match self.topology {
+            Topology::Series => (sum as f64 / currents.len() as f64).round() as i32,
+            Topology::Parallel => sum.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+        })
+    }
+
+    /// Returns the highest temperature of the modules
+    /// Unit: degC
+    fn temperature(&self) -> Result<f64> {
+        Ok(self
+            .read(BatteryField::Temperature, |module| module.temperature())?
+            .into_iter()
+            .fold(f64::MIN, f64::max))
+    }
+
+    /// Unit: mAh
+    fn remaining_capacity(&self) -> Result<u32> {
+        self.capacity(BatteryField::RemainingCapacity, |module| {
+            module.remaining_capacity()
+        })
+    }
+
+    /// Unit: mAh
+    fn full_charge_capacity(&self) -> Result<u32> {
+        self.capacity(BatteryField::FullChargeCapacity, |module| {
+            module.full_charge_capacity()
+        })
+    }
+
+    /// Unit: mAh
+    fn design_capacity(&self) -> Result<u32> {
+        self.capacity(BatteryField::DesignCapacity, |module| {
+            module.design_capacity()
+        })
+    }
+
+    /// In parallel, weighted by design capacity
+    /// Unit: %
+    fn absolute_state_of_charge(&self) -> Result<u32> {
+        self.percentage(
+            BatteryField::AbsoluteStateOfCharge,
+            |module| module.absolute_state_of_charge(),
+            |module| module.design_capacity(),
+        )
+    }
+
+    /// In parallel, weighted by full charge capacity
+    /// Unit: %
+    fn relative_state_of_charge(&self) -> Result<u32> {
+        self.percentage(
+            BatteryField::RelativeStateOfCharge,
+            |module| module.relative_state_of_charge(),
+            |module| module.full_charge_capacity(),
+        )
+    }
+
+    /// In parallel, weighted by design capacity
+    /// Unit: %
+    fn state_of_health(&self) -> Result<u32> {
+        self.percentage(
+            BatteryField::StateOfHealth,
+            |module| module.state_of_health(),
+            |module| module.design_capacity(),
+        )
+    }
+
+    /// Unit: mV
+    fn bm_voltage(&self) -> Result<u32> {
+        let voltages = self.read(BatteryField::BmVoltage, |module| module.bm_voltage())?;
+        let sum: u32 = voltages.iter().sum();
+        Ok(match self.topology {
+            Topology::Series => sum,
+            Topology::Parallel => (sum as f64 / voltages.len() as f64).round() as u32,
+        })
+    }
+
+    fn fail_status_1(&self) -> Result<FailStatus1> {
+        self.merged(
+            BatteryField::FailStatus1,
+            |module| module.fail_status_1(),
+            |status| status.0,
+        )
+        .map(FailStatus1)
+    }
+
+    fn fail_status_2(&self) -> Result<FailStatus2> {
+        self.merged(
+            BatteryField::FailStatus2,
+            |module| module.fail_status_2(),
+            |status| status.0,
+        )
+        .map(FailStatus2)
+    }
+
+    fn fail_status_3(&self) -> Result<FailStatus3> {
+        self.merged(
+            BatteryField::FailStatus3,
+            |module| module.fail_status_3(),
+            |status| status.0,
+        )
+        .map(FailStatus3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn module(relative_state_of_charge: u32, full_charge_capacity: u32) -> BatterySnapshot {
+        BatterySnapshot {
+            cell_voltages: Some(vec![3300, 3310]),
+            current: Some(-10000),
+            temperature: Some(25.0),
+            remaining_capacity: Some(full_charge_capacity * relative_state_of_charge / 100),
+            full_charge_capacity: Some(full_charge_capacity),
+            design_capacity: Some(10000),
+            absolute_state_of_charge: Some(relative_state_of_charge),
+            relative_state_of_charge: Some(relative_state_of_charge),
+            state_of_health: Some(full_charge_capacity / 100),
+            bm_voltage: Some(26400),
+            fail_status_1: Some(FailStatus1(0)),
+            fail_status_2: Some(FailStatus2(0)),
+            fail_status_3: Some(FailStatus3(0)),
+        }
+    }
+
+    #[test]
+    fn test_series() {
+        let mut modules = vec![module(80, 10000), module(40, 5000)];
+        modules[1].temperature = Some(38.5);
+        modules[1].current = Some(-10010);
+        let pack = Pack::new(Topology::Series, modules);
+        assert_eq!(pack.bm_voltage().unwrap(), 52800);
+        assert_eq!(pack.current().unwrap(), -10005);
+        assert_eq!(pack.temperature().unwrap(), 38.5);
+        assert_eq!(pack.full_charge_capacity().unwrap(), 5000);
+        assert_eq!(pack.cell_voltages().unwrap(), vec![3300, 3310, 3300, 3310]);
+        // the second module empties the string first: 2000 / 5000
+        assert_eq!(pack.remaining_capacity().unwrap(), 2000);
+        assert_eq!(pack.relative_state_of_charge().unwrap(), 40);
+        assert_eq!(pack.absolute_state_of_charge().unwrap(), 40);
+        assert_eq!(pack.state_of_health().unwrap(), 50);
+    }
+
+    #[test]
+    fn test_parallel() {
+        let pack = Pack::new(
+            Topology::Parallel,
+            vec![module(80, 10000), module(60, 10000), module(70, 10000)],
+        );
+        assert_eq!(pack.current().unwrap(), -30000);
+        assert_eq!(pack.bm_voltage().unwrap(), 26400);
+        assert_eq!(pack.remaining_capacity().unwrap(), 21000);
+        assert_eq!(pack.full_charge_capacity().unwrap(), 30000);
+        assert_eq!(pack.relative_state_of_charge().unwrap(), 70);
+
+        let pack = pack.with_state_of_charge_mode(StateOfChargeMode::Weakest);
+        assert_eq!(pack.relative_state_of_charge().unwrap(), 60);
+    }
+
+    #[test]
+    fn test_faults() {
+        let mut modules = vec![module(80, 10000), module(80, 10000), module(80, 10000)];
+        // over charge protection in modules 0 and 2, fully charge detection in module 2
+        modules[0].fail_status_1 = Some(FailStatus1(0x04));
+        modules[2].fail_status_1 = Some(FailStatus1(0x44));
+        let pack = Pack::new(Topology::Series, modules);
+        assert_eq!(pack.fail_status_1().unwrap(), FailStatus1(0x44));
+        assert_eq!(
+            pack.raised_faults(),
+            vec![
+                (FailStatusItem::OverChargeProtection, vec![0, 2]),
+                (FailStatusItem::FullyChargeDetection, vec![2]),
+            ]
+        );
+        assert_eq!(
+            pack.fail_status(FailStatusItem::OverChargeProtection),
+            FailState::Ng
+        );
+    }
+
+    #[test]
+    fn test_unavailable() {
+        let mut modules = vec![module(80, 10000), module(80, 10000)];
+        modules[1].temperature = None;
+        let pack = Pack::new(Topology::Series, modules);
+        assert!(matches!(
+            pack.temperature(),
+            Err(Error::FieldNotAvailable(BatteryField::Temperature))
+        ));
+        let empty: Pack<BatterySnapshot> = Pack::new(Topology::Parallel, vec![]);
+        assert!(empty.current().is_err());
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{battery_module::BatteryField, battery_state::BatteryState};
+
+/// Limits a reading must stay within to be plausible
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlausibilityLimits {
+    /// Unit: mV
+    pub min_cell_voltage: u32,
+    /// Unit: mV
+    pub max_cell_voltage: u32,
+    /// How far the sum of the cell voltages may be from `bm_voltage`
+    /// Unit: mV
+    pub cell_sum_tolerance: u32,
+    /// How far `remaining_capacity` may exceed `full_charge_capacity`
+    /// Unit: mAh
+    pub capacity_tolerance: u32,
+    /// How far `relative_state_of_charge` may be from the ratio of the capacities
+    /// Unit: %
+    pub state_of_charge_tolerance: f64,
+    /// Unit: degC
+    pub min_temperature: f64,
+    /// Unit: degC
+    pub max_temperature: f64,
+    /// Largest change of a cell voltage between consecutive samples
+    /// Unit: mV
+    pub max_cell_voltage_change: u32,
+    /// Largest change of `bm_voltage` between consecutive samples
+    /// Unit: mV
+    pub max_bm_voltage_change: u32,
+    /// Largest change of the temperature between consecutive samples
+    /// Unit: degC
+    pub max_temperature_change: f64,
+    /// Largest change of `relative_state_of_charge` between consecutive samples
+    /// Unit: %
+    pub max_state_of_charge_change: u32,
+    /// Samples further apart than this are not compared
+    pub max_interval: Duration,
+}
+
+impl Default for PlausibilityLimits {
+    fn default() -> Self {
+        Self {
+            min_cell_voltage: 2000,
+            max_cell_voltage: 4200,
+            cell_sum_tolerance: 500,
+            capacity_tolerance: 100,
+            state_of_charge_tolerance: 5.0,
+            min_temperature: -40.0,
+            max_temperature: 100.0,
+            max_cell_voltage_change: 300,
+            max_bm_voltage_change: 2000,
+            max_temperature_change: 5.0,
+            max_state_of_charge_change: 5,
+            max_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Why a reading is implausible
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Implausibility {
+    CellVoltageOutOfRange {
+        cell: usize,
+        /// Unit: mV
+        voltage: u32,
+    },
+    CellSumMismatch {
+        /// Unit: mV
+        cell_sum: u32,
+        /// Unit: mV
+        bm_voltage: u32,
+    },
+    RemainingAboveFullCharge {
+        /// Unit: mAh
+        remaining_capacity: u32,
+        /// Unit: mAh
+        full_charge_capacity: u32,
+    },
+    StateOfChargeMismatch {
+        /// Unit: %
+        relative_state_of_charge: u32,
+        /// `remaining_capacity` over `full_charge_capacity`
+        /// Unit: %
+        expected: f64,
+    },
+    TemperatureOutOfRange {
+        /// Unit: degC
+        temperature: f64,
+    },
+    ChangeTooLarge {
+        field: BatteryField,
+        /// Absolute change from the previous plausible sample
+        change: f64,
+    },
+}
+
+impl fmt::Display for Implausibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Implausibility::CellVoltageOutOfRange { cell, voltage } => {
+                write!(f, "cell {cell} voltage {voltage} mV is out of range")
+            }
+            Implausibility::CellSumMismatch {
+                cell_sum,
+                bm_voltage,
+            } => write!(
+                f,
+                "sum of cell voltages {cell_sum} mV does not match module voltage {bm_voltage} mV"
+            ),
+            Implausibility::RemainingAboveFullCharge {
+                remaining_capacity,
+                full_charge_capacity,
+            } => write!(
+                f,
+                "remaining capacity {remaining_capacity} mAh exceeds full charge capacity {full_charge_capacity} mAh"
+            ),
+            Implausibility::StateOfChargeMismatch {
+                relative_state_of_charge,
+                expected,
+            } => write!(
+                f,
+                "relative state of charge {relative_state_of_charge} % does not match capacities ({expected:.1} %)"
+            ),
+            Implausibility::TemperatureOutOfRange { temperature } => {
+                write!(f, "temperature {temperature} degC is out of range")
+            }
+            Implausibility::ChangeTooLarge { field, change } => {
+                write!(f, "{field} changed by {change} since the previous sample")
+            }
+        }
+    }
+}
+
+/// Result of checking a reading
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Plausible,
+    /// Every limit the reading violates
+    Implausible(Vec<Implausibility>),
+}
+
+impl Verdict {
+    pub fn is_plausible(&self) -> bool {
+        matches!(self, Verdict::Plausible)
+    }
+
+    /// Returns the reasons, empty if plausible
+    pub fn reasons(&self) -> &[Implausibility] {
+        match self {
+            Verdict::Plausible => &[],
+            Verdict::Implausible(reasons) => reasons,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Reference {
+    at: SystemTime,
+    cell_voltages: Option<Vec<u32>>,
+    bm_voltage: Option<u32>,
+    temperature: Option<f64>,
+    relative_state_of_charge: Option<u32>,
+}
+
+/// Checks decoded readings against physical limits and against the previous reading
+///
+/// The frame checksum is a one-byte XOR, so a corrupted frame may still decode.
+/// Fields which cannot be read are not checked. Changes are compared with the
+/// latest plausible reading only, so one corrupted reading does not make the
+/// next good one look implausible.
+///
+/// ```
+/// use std::time::SystemTime;
+/// use fortelion::{plausibility::*, BatterySnapshot};
+///
+/// let mut validator = PlausibilityValidator::new();
+/// let state = BatterySnapshot {
+///     remaining_capacity: Some(12000),
+///     full_charge_capacity: Some(10000),
+///     ..Default::default()
+/// };
+/// let verdict = validator.check(&state, SystemTime::now());
+/// assert!(!verdict.is_plausible());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PlausibilityValidator {
+    limits: PlausibilityLimits,
+    reference: Option<Reference>,
+}
+
+impl PlausibilityValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limits(mut self, limits: PlausibilityLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &PlausibilityLimits {
+        &self.limits
+    }
+
+    /// Checks a reading taken at `at`
+    pub fn check(&mut self, state: &(impl BatteryState + ?Sized), at: SystemTime) -> Verdict {
+        let limits = &self.limits;
+        let current = Reference {
+            at,
+            cell_voltages: state.cell_voltages().ok(),
+            bm_voltage: state.bm_voltage().ok(),
+            temperature: state.temperature().ok(),
+            relative_state_of_charge: state.relative_state_of_charge().ok(),
+        };
+        let mut reasons = vec![];
+
+        if let Some(cell_voltages) = &current.cell_voltages {
+            for (cell, &voltage) in cell_voltages.iter().enumerate() {
+                if !(limits.min_cell_voltage..=limits.max_cell_voltage).contains(&voltage) {
+                    reasons.push(Implausibility::CellVoltageOutOfRange { cell, voltage });
+                }
+            }
+            if let Some(bm_voltage) = current.bm_voltage {
+                let cell_sum = cell_voltages
+                    .iter()
+                    .fold(0u32, |sum, voltage| sum.saturating_add(*voltage));
+                if cell_sum.abs_diff(bm_voltage) > limits.cell_sum_tolerance {
+                    reasons.push(Implausibility::CellSumMismatch {
+                        cell_sum,
+                        bm_voltage,
+                    });
+                }
+            }
+        }
+
+        if let (Ok(remaining_capacity), Ok(full_charge_capacity)) =
+            (state.remaining_capacity(), state.full_charge_capacity())
+        {
+            if remaining_capacity > full_charge_capacity.saturating_add(limits.capacity_tolerance) {
+                reasons.push(Implausibility::RemainingAboveFullCharge {
+                    remaining_capacity,
+                    full_charge_capacity,
+                });
+            } else if let (Some(relative_state_of_charge), true) =
+                (current.relative_state_of_charge, full_charge_capacity > 0)
+            {
+                let expected =
+                    (remaining_capacity as f64 / full_charge_capacity as f64 * 100.0).min(100.0);
+                if (relative_state_of_charge as f64 - expected).abs()
+                    > limits.state_of_charge_tolerance
+                {
+                    reasons.push(Implausibility::StateOfChargeMismatch {
+                        relative_state_of_charge,
+                        expected,
+                    });
+                }
+            }
+        }
+
+        if let Some(temperature) = current.temperature {
+            if !(limits.min_temperature..=limits.max_temperature).contains(&temperature) {
+                reasons.push(Implausibility::TemperatureOutOfRange { temperature });
+            }
+        }
+
+        if let Some(reference) = self.reference.as_ref().filter(|reference| {
+            at.duration_since(reference.at)
+                .is_ok_and(|interval| interval <= limits.max_interval)
+        }) {
+            reasons.extend(changes(reference, &current, limits));
+        }
+
+        if reasons.is_empty() {
+            self.reference = Some(current);
+            Verdict::Plausible
+        } else {
+            Verdict::Implausible(reasons)
+        }
+    }
+
+    /// Forgets the previous reading, e.g. after reconnecting
+    pub fn reset(&mut self) {
+        self.reference = None;
+    }
+}
+
+fn changes(from: &Reference, to: &Reference, limits: &PlausibilityLimits) -> Vec<Implausibility> {
+    let mut reasons = vec![];
+    let mut check = |field, change: f64, max: f64| {
+        if change > max {
+            reasons.push(Implausibility::ChangeTooLarge { field, change });
+        }
+    };
+    if let (Some(from), Some(to)) = (&from.cell_voltages, &to.cell_voltages) {
+        let change = from
+            .iter()
+            .zip(to)
+            .map(|(from, to)| from.abs_diff(*to))
+            .max()
+            .unwrap_or_default();
+        check(
+            BatteryField::CellVoltages,
+            change as f64,
+            limits.max_cell_voltage_change as f64,
+        );
+    }
+    if let (Some(from), Some(to)) = (from.bm_voltage, to.bm_voltage) {
+        check(
+            BatteryField::BmVoltage,
+            from.abs_diff(to) as f64,
+            limits.max_bm_voltage_change as f64,
+        );
+    }
+    if let (Some(from), Some(to)) = (from.temperature, to.temperature) {
+        check(
+            BatteryField::Temperature,
+            (to - from).abs(),
+            limits.max_temperature_change,
+        );
+    }
+    if let (Some(from), Some(to)) = (from.relative_state_of_charge, to.relative_state_of_charge) {
+        check(
+            BatteryField::RelativeStateOfCharge,
+            from.abs_diff(to) as f64,
+            limits.max_state_of_charge_change as f64,
+        );
+    }
+    reasons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_snapshot::BatterySnapshot;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn snapshot() -> BatterySnapshot {
+        BatterySnapshot {
+            cell_voltages: Some(vec![3300; 8]),
+            current: Some(-5000),
+            temperature: Some(25.0),
+            remaining_capacity: Some(8000),
+            full_charge_capacity: Some(10000),
+            relative_state_of_charge: Some(80),
+            bm_voltage: Some(26400),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_static_limits() {
+        let mut validator = PlausibilityValidator::new();
+        assert_eq!(validator.check(&snapshot(), at(0)), Verdict::Plausible);
+
+        let mut state = snapshot();
+        state.cell_voltages = Some(vec![3300, 3300, 3300, 3300, 3300, 3300, 3300, 33000]);
+        state.temperature = Some(-200.0);
+        state.relative_state_of_charge = Some(20);
+        let verdict = validator.check(&state, at(100));
+        assert_eq!(
+            verdict.reasons(),
+            &[
+                Implausibility::CellVoltageOutOfRange {
+                    cell: 7,
+                    voltage: 33000
+                },
+                Implausibility::CellSumMismatch {
+                    cell_sum: 56100,
+                    bm_voltage: 26400
+                },
+                Implausibility::StateOfChargeMismatch {
+                    relative_state_of_charge: 20,
+                    expected: 80.0
+                },
+                Implausibility::TemperatureOutOfRange {
+                    temperature: -200.0
+                },
+            ]
+        );
+        assert_eq!(
+            verdict.reasons()[0].to_string(),
+            "cell 7 voltage 33000 mV is out of range"
+        );
+
+        state = snapshot();
+        state.remaining_capacity = Some(12000);
+        assert_eq!(
+            validator.check(&state, at(200)).reasons(),
+            &[Implausibility::RemainingAboveFullCharge {
+                remaining_capacity: 12000,
+                full_charge_capacity: 10000
+            }]
+        );
+        // a tolerance large enough to disable the check
+        let mut disabled = PlausibilityValidator::new().with_limits(PlausibilityLimits {
+            capacity_tolerance: u32::MAX,
+            ..Default::default()
+        });
+        assert!(!disabled
+            .check(&state, at(200))
+            .reasons()
+            .iter()
+            .any(|reason| matches!(reason, Implausibility::RemainingAboveFullCharge { .. })));
+        // nothing readable, nothing to check
+        assert!(validator
+            .check(&BatterySnapshot::default(), at(300))
+            .is_plausible());
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut validator = PlausibilityValidator::new();
+        assert!(validator.check(&snapshot(), at(0)).is_plausible());
+
+        let mut state = snapshot();
+        state.temperature = Some(45.0);
+        assert_eq!(
+            validator.check(&state, at(1)).reasons(),
+            &[Implausibility::ChangeTooLarge {
+                field: BatteryField::Temperature,
+                change: 20.0
+            }]
+        );
+        // compared with the last plausible reading, not the rejected one
+        state.temperature = Some(26.0);
+        assert!(validator.check(&state, at(2)).is_plausible());
+
+        // too far apart to compare
+        state.temperature = Some(45.0);
+        assert!(validator.check(&state, at(100)).is_plausible());
+    }
+}
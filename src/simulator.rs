@@ -0,0 +1,406 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battery_module::{BatteryField, BatteryModule},
+    battery_snapshot::BatterySnapshot,
+    battery_state::BatteryState,
+    error::Result,
+    fail_status::*,
+};
+
+const NUMBER_OF_CELLS: usize = 8;
+
+/// Open circuit voltage of a cell against relative state of charge
+/// Points are `(state of charge [%], voltage [mV])` sorted by state of charge,
+/// and the voltage between them is linearly interpolated.
+/// Serialized as an array of `[state of charge, voltage]` pairs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OcvCurve(pub Vec<(f64, u32)>);
+
+impl OcvCurve {
+    pub fn voltage(&self, state_of_charge: f64) -> f64 {
+        let points = &self.0;
+        match points.iter().position(|(soc, _)| *soc >= state_of_charge) {
+            None => points.last().map(|(_, v)| *v as f64).unwrap_or_default(),
+            Some(0) => points[0].1 as f64,
+            Some(i) => {
+                let (soc0, v0) = points[i - 1];
+                let (soc1, v1) = points[i];
+                v0 as f64 + (v1 as f64 - v0 as f64) * (state_of_charge - soc0) / (soc1 - soc0)
+            }
+        }
+    }
+}
+
+impl Default for OcvCurve {
+    /// Typical curve of an olivine-type lithium iron phosphate cell
+    fn default() -> Self {
+        Self(vec![
+            (0.0, 2500),
+            (5.0, 3000),
+            (10.0, 3180),
+            (20.0, 3240),
+            (50.0, 3290),
+            (80.0, 3330),
+            (95.0, 3350),
+            (100.0, 3450),
+        ])
+    }
+}
+
+/// Missing fields take the default values when deserialized.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulatorConfig {
+    /// Unit: mAh
+    pub design_capacity: u32,
+    /// Unit: %
+    pub state_of_health: u32,
+    /// Unit: %
+    pub initial_state_of_charge: f64,
+    /// Current flowing into the module, negative while discharging
+    /// Unit: mA
+    pub load_current: i32,
+    /// Unit: degC
+    pub temperature: f64,
+    pub ocv_curve: OcvCurve,
+    /// Internal resistance of each cell
+    /// Unit: mOhm
+    pub cell_internal_resistance: f64,
+    /// Offsets added to each cell voltage, to simulate unbalance
+    /// Unit: mV
+    pub cell_voltage_offsets: [i32; NUMBER_OF_CELLS],
+    /// Cell voltage below which low voltage is detected
+    /// Unit: mV
+    pub low_voltage_threshold: u32,
+    /// Cell voltage above which over charge protection trips
+    /// Unit: mV
+    pub over_charge_threshold: u32,
+    /// Relative state of charge below which fully charge detection is cleared
+    /// Unit: %
+    pub recharge_state_of_charge: f64,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            design_capacity: 10000,
+            state_of_health: 100,
+            initial_state_of_charge: 80.0,
+            load_current: -5000,
+            temperature: 25.0,
+            ocv_curve: OcvCurve::default(),
+            cell_internal_resistance: 2.0,
+            cell_voltage_offsets: [0; NUMBER_OF_CELLS],
+            low_voltage_threshold: 3000,
+            over_charge_threshold: 3650,
+            recharge_state_of_charge: 95.0,
+        }
+    }
+}
+
+/// Virtual FORTELION module
+///
+/// Integrates the load current to drain and charge the module,
+/// derives cell voltages from an OCV curve and raises fail status bits
+/// when thresholds are crossed.
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    config: SimulatorConfig,
+    remaining_capacity: f64,
+    load_current: i32,
+    /// FailStatus1, FailStatus2 and FailStatus3
+    fail_status: [u8; 3],
+    elapsed: Duration,
+}
+
+impl Simulator {
+    pub fn new(config: SimulatorConfig) -> Self {
+        let mut simulator = Self {
+            remaining_capacity: 0.0,
+            load_current: config.load_current,
+            fail_status: [0; 3],
+            elapsed: Duration::ZERO,
+            config,
+        };
+        simulator.remaining_capacity = simulator.full_charge_capacity_mah()
+            * simulator.config.initial_state_of_charge.clamp(0.0, 100.0)
+            / 100.0;
+        simulator.update_fail_status();
+        simulator
+    }
+
+    pub fn config(&self) -> &SimulatorConfig {
+        &self.config
+    }
+
+    /// Sets current flowing into the module, negative while discharging
+    /// Unit: mA
+    pub fn set_load_current(&mut self, load_current: i32) {
+        self.load_current = load_current;
+    }
+
+    pub fn set_temperature(&mut self, temperature: f64) {
+        self.config.temperature = temperature;
+    }
+
+    /// Returns simulated time
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Advances the simulation
+    pub fn step(&mut self, dt: Duration) {
+        let current = self.effective_current() as f64;
+        self.remaining_capacity = (self.remaining_capacity + current * dt.as_secs_f64() / 3600.0)
+            .clamp(0.0, self.full_charge_capacity_mah());
+        self.elapsed = self.elapsed.saturating_add(dt);
+        self.update_fail_status();
+    }
+
+    fn full_charge_capacity_mah(&self) -> f64 {
+        self.config.design_capacity as f64 * self.config.state_of_health as f64 / 100.0
+    }
+
+    fn relative_state_of_charge_percent(&self) -> f64 {
+        let full_charge_capacity = self.full_charge_capacity_mah();
+        if full_charge_capacity > 0.0 {
+            100.0 * self.remaining_capacity / full_charge_capacity
+        } else {
+            0.0
+        }
+    }
+
+    /// The charger stops once fully charged, and the module cuts off discharge when empty.
+    fn effective_current(&self) -> i32 {
+        let fully_charged =
+            FailStatus1(self.fail_status[0]).fully_charge_detection() == FailState::Ng;
+        if (self.load_current > 0 && fully_charged)
+            || (self.load_current < 0 && self.remaining_capacity <= 0.0)
+        {
+            0
+        } else {
+            self.load_current
+        }
+    }
+
+    fn cell_voltage(&self, offset: i32) -> u32 {
+        let ocv = self
+            .config
+            .ocv_curve
+            .voltage(self.relative_state_of_charge_percent());
+        let drop = self.effective_current() as f64 * self.config.cell_internal_resistance / 1000.0;
+        (ocv + drop + offset as f64).round().max(0.0) as u32
+    }
+
+    fn cell_voltages_mv(&self) -> Vec<u32> {
+        self.config
+            .cell_voltage_offsets
+            .iter()
+            .map(|offset| self.cell_voltage(*offset))
+            .collect()
+    }
+
+    fn update_fail_status(&mut self) {
+        let state_of_charge = self.relative_state_of_charge_percent();
+        if self.full_charge_capacity_mah() - self.remaining_capacity < 0.5 {
+            self.set_fault(FailStatusItem::FullyChargeDetection, true);
+        } else if state_of_charge < self.config.recharge_state_of_charge {
+            self.set_fault(FailStatusItem::FullyChargeDetection, false);
+        }
+
+        let cell_voltages = self.cell_voltages_mv();
+        let min = cell_voltages.iter().copied().min().unwrap_or_default();
+        let max = cell_voltages.iter().copied().max().unwrap_or_default();
+        self.set_fault(
+            FailStatusItem::LowVoltageDetection,
+            min < self.config.low_voltage_threshold,
+        );
+        self.set_fault(
+            FailStatusItem::OverChargeProtection,
+            max > self.config.over_charge_threshold,
+        );
+    }
+
+    /// Sets or clears the bit `item` is reported in
+    fn set_fault(&mut self, item: FailStatusItem, value: bool) {
+        let (index, position) = item.bit_position();
+        let bit = 1 << position;
+        if value {
+            self.fail_status[index] |= bit;
+        } else {
+            self.fail_status[index] &= !bit;
+        }
+    }
+}
+
+impl BatteryState for Simulator {
+    fn cell_voltages(&self) -> Result<Vec<u32>> {
+        Ok(self.cell_voltages_mv())
+    }
+
+    fn current(&self) -> Result<i32> {
+        Ok(self.effective_current())
+    }
+
+    fn temperature(&self) -> Result<f64> {
+        Ok(self.config.temperature)
+    }
+
+    fn remaining_capacity(&self) -> Result<u32> {
+        Ok(self.remaining_capacity.round() as u32)
+    }
+
+    fn full_charge_capacity(&self) -> Result<u32> {
+        Ok(self.full_charge_capacity_mah().round() as u32)
+    }
+
+    fn design_capacity(&self) -> Result<u32> {
+        Ok(self.config.design_capacity)
+    }
+
+    fn absolute_state_of_charge(&self) -> Result<u32> {
+        Ok((100.0 * self.remaining_capacity / self.config.design_capacity as f64).round() as u32)
+    }
+
+    fn relative_state_of_charge(&self) -> Result<u32> {
+        Ok(self.relative_state_of_charge_percent().round() as u32)
+    }
+
+    fn state_of_health(&self) -> Result<u32> {
+        Ok(self.config.state_of_health)
+    }
+
+    fn bm_voltage(&self) -> Result<u32> {
+        Ok(self.cell_voltages_mv().into_iter().sum())
+    }
+
+    fn fail_status_1(&self) -> Result<FailStatus1> {
+        Ok(FailStatus1(self.fail_status[0]))
+    }
+
+    fn fail_status_2(&self) -> Result<FailStatus2> {
+        Ok(FailStatus2(self.fail_status[1]))
+    }
+
+    fn fail_status_3(&self) -> Result<FailStatus3> {
+        Ok(FailStatus3(self.fail_status[2]))
+    }
+}
+
+impl BatteryModule for Simulator {
+    fn supported_fields(&self) -> Vec<BatteryField> {
+        use strum::IntoEnumIterator;
+        BatteryField::iter().collect()
+    }
+
+    fn read(&mut self, fields: &[BatteryField]) -> Result<BatterySnapshot> {
+        BatterySnapshot::try_from_battery_state(self, fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ocv_curve() {
+        let curve = OcvCurve(vec![(0.0, 3000), (50.0, 3200), (100.0, 3400)]);
+        assert_eq!(curve.voltage(-10.0), 3000.0);
+        assert_eq!(curve.voltage(0.0), 3000.0);
+        assert_eq!(curve.voltage(25.0), 3100.0);
+        assert_eq!(curve.voltage(50.0), 3200.0);
+        assert_eq!(curve.voltage(75.0), 3300.0);
+        assert_eq!(curve.voltage(120.0), 3400.0);
+    }
+
+    #[test]
+    fn test_discharge() {
+        let mut simulator = Simulator::new(SimulatorConfig {
+            design_capacity: 10000,
+            initial_state_of_charge: 50.0,
+            load_current: -10000,
+            ..Default::default()
+        });
+        assert_eq!(simulator.remaining_capacity().unwrap(), 5000);
+        assert_eq!(simulator.current().unwrap(), -10000);
+
+        simulator.step(Duration::from_secs(1800));
+        assert_eq!(simulator.remaining_capacity().unwrap(), 0);
+        assert_eq!(simulator.relative_state_of_charge().unwrap(), 0);
+        assert_eq!(simulator.elapsed(), Duration::from_secs(1800));
+        assert_eq!(
+            simulator.fail_status(FailStatusItem::LowVoltageDetection),
+            FailState::Ng
+        );
+        // discharge is cut off when empty
+        assert_eq!(simulator.current().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_charge() {
+        let mut simulator = Simulator::new(SimulatorConfig {
+            design_capacity: 10000,
+            state_of_health: 90,
+            initial_state_of_charge: 50.0,
+            load_current: 9000,
+            ..Default::default()
+        });
+        assert_eq!(simulator.full_charge_capacity().unwrap(), 9000);
+        assert_eq!(
+            simulator.fail_status(FailStatusItem::FullyChargeDetection),
+            FailState::Ok
+        );
+        let voltage_before = simulator.bm_voltage().unwrap();
+
+        simulator.step(Duration::from_secs(1200));
+        assert_eq!(simulator.relative_state_of_charge().unwrap(), 83);
+        assert!(simulator.bm_voltage().unwrap() > voltage_before);
+
+        simulator.step(Duration::from_secs(1200));
+        assert_eq!(simulator.relative_state_of_charge().unwrap(), 100);
+        assert_eq!(simulator.absolute_state_of_charge().unwrap(), 90);
+        assert_eq!(
+            simulator.fail_status(FailStatusItem::FullyChargeDetection),
+            FailState::Ng
+        );
+        // the charger stops when fully charged
+        assert_eq!(simulator.current().unwrap(), 0);
+
+        // cleared with hysteresis
+        simulator.set_load_current(-9000);
+        simulator.step(Duration::from_secs(120));
+        assert_eq!(
+            simulator.fail_status(FailStatusItem::FullyChargeDetection),
+            FailState::Ng
+        );
+        simulator.step(Duration::from_secs(240));
+        assert_eq!(
+            simulator.fail_status(FailStatusItem::FullyChargeDetection),
+            FailState::Ok
+        );
+    }
+
+    #[test]
+    fn test_cell_voltages() {
+        let mut offsets = [0; NUMBER_OF_CELLS];
+        offsets[3] = -40;
+        let simulator = Simulator::new(SimulatorConfig {
+            initial_state_of_charge: 50.0,
+            load_current: 0,
+            cell_voltage_offsets: offsets,
+            ..Default::default()
+        });
+        let cell_voltages = simulator.cell_voltages().unwrap();
+        assert_eq!(cell_voltages.len(), NUMBER_OF_CELLS);
+        assert_eq!(cell_voltages[0], 3290);
+        assert_eq!(cell_voltages[3], 3250);
+        assert_eq!(
+            simulator.bm_voltage().unwrap(),
+            cell_voltages.iter().sum::<u32>()
+        );
+    }
+}
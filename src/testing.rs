@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+
+use crate::{
+    battery_state::BatteryState,
+    error::{Error, Result},
+    uart::{Command, CommandFrame, DataFrame, Transport},
+};
+
+struct Expectation {
+    request: Vec<u8>,
+    response: Vec<u8>,
+}
+
+/// `Transport` which checks what is sent against queued expectations
+/// and answers each of them with canned bytes
+///
+/// Sending bytes which do not match the next expectation panics.
+/// Receiving more bytes than have been answered fails with a timeout, as `Port` does.
+///
+/// ```
+/// use fortelion::{testing::MockTransport, uart::*, BatteryState, BatterySnapshot};
+///
+/// let mut transport = MockTransport::new();
+/// let state = BatterySnapshot {
+///     current: Some(-1200),
+///     ..Default::default()
+/// };
+/// transport.expect_state(Command::Current, &state).unwrap();
+///
+/// let mut module = UartBatteryModule::new(transport);
+/// let data_frame = module.request(Command::Current).unwrap();
+/// let view = DataFrameView::try_new(&data_frame).unwrap();
+/// assert_eq!(view.current().unwrap(), -1200);
+/// module.into_inner().assert_done();
+/// ```
+#[derive(Default)]
+pub struct MockTransport {
+    expectations: VecDeque<Expectation>,
+    sending: Vec<u8>,
+    sent: Vec<u8>,
+    responses: VecDeque<u8>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expects `request` to be sent, and answers it with `response`
+    pub fn expect_bytes(
+        &mut self,
+        request: impl Into<Vec<u8>>,
+        response: impl Into<Vec<u8>>,
+    ) -> &mut Self {
+        self.expectations.push_back(Expectation {
+            request: request.into(),
+            response: response.into(),
+        });
+        self
+    }
+
+    /// Expects the command frame of `command`, and answers it with `response`
+    /// An empty response makes the receiver time out.
+    pub fn expect(&mut self, command: Command, response: impl Into<Vec<u8>>) -> &mut Self {
+        self.expect_bytes(CommandFrame::new(command).as_ref(), response)
+    }
+
+    /// Expects the command frame of `command`, and answers it with a valid data frame carrying `data`
+    pub fn expect_data(&mut self, command: Command, data: &[u8]) -> Result<&mut Self> {
+        let data_frame = DataFrame::try_from_data(command, data)?;
+        Ok(self.expect(command, data_frame.as_ref()))
+    }
+
+    /// Expects the command frame of `command`, and answers it with a data frame encoded from `state`
+    pub fn expect_state(
+        &mut self,
+        command: Command,
+        state: &(impl BatteryState + ?Sized),
+    ) -> Result<&mut Self> {
+        let data_frame = DataFrame::encode(command, state)?;
+        Ok(self.expect(command, data_frame.as_ref()))
+    }
+
+    /// Returns all the bytes sent so far
+    pub fn sent(&self) -> &[u8] {
+        &self.sent
+    }
+
+    /// Returns the number of expectations not consumed yet
+    pub fn remaining(&self) -> usize {
+        self.expectations.len()
+    }
+
+    /// Panics unless every expectation has been consumed and every response has been received
+    #[track_caller]
+    pub fn assert_done(&self) {
+        assert!(
+            self.expectations.is_empty(),
+            "{} expectation(s) not consumed, next: {:02x?}",
+            self.expectations.len(),
+            self.expectations.front().map(|e| &e.request)
+        );
+        assert!(
+            self.sending.is_empty(),
+            "incomplete request sent: {:02x?}",
+            self.sending
+        );
+        assert!(
+            self.responses.is_empty(),
+            "{} response byte(s) not received",
+            self.responses.len()
+        );
+    }
+}
+
+impl Transport for MockTransport {
+    #[track_caller]
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        self.sent.extend_from_slice(bytes);
+        self.sending.extend_from_slice(bytes);
+        while let Some(expectation) = self.expectations.front() {
+            let len = expectation.request.len().min(self.sending.len());
+            assert_eq!(
+                self.sending[..len],
+                expectation.request[..len],
+                "unexpected bytes sent"
+            );
+            if len < expectation.request.len() {
+                break;
+            }
+            self.sending.drain(..len);
+            let expectation = self.expectations.pop_front().unwrap();
+            self.responses.extend(expectation.response);
+        }
+        assert!(
+            self.sending.is_empty(),
+            "unexpected bytes sent with no expectation left: {:02x?}",
+            self.sending
+        );
+        Ok(())
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+        if self.responses.len() < buf.len() {
+            self.responses.clear();
+            return Err(Error::UartFailedToReceive(
+                std::io::ErrorKind::TimedOut.into(),
+            ));
+        }
+        for byte in buf.iter_mut() {
+            *byte = self.responses.pop_front().unwrap();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uart::{DataFrameView, UartBatteryModule};
+
+    #[test]
+    fn test_mock_transport() {
+        let mut transport = MockTransport::new();
+        transport
+            .expect_data(Command::StateOfHealth, &[97])
+            .unwrap()
+            .expect(Command::Current, vec![]);
+        assert_eq!(transport.remaining(), 2);
+
+        let mut module = UartBatteryModule::new(transport);
+        let data_frame = module.request(Command::StateOfHealth).unwrap();
+        let view = DataFrameView::try_new(&data_frame).unwrap();
+        assert_eq!(view.state_of_health().unwrap(), 97);
+
+        // timed out
+        assert!(matches!(
+            module.request(Command::Current),
+            Err(Error::UartFailedToReceive(_))
+        ));
+
+        let transport = module.into_inner();
+        let mut expected = CommandFrame::new(Command::StateOfHealth).as_ref().to_vec();
+        expected.extend_from_slice(CommandFrame::new(Command::Current).as_ref());
+        assert_eq!(transport.sent(), &expected[..]);
+        transport.assert_done();
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected bytes sent")]
+    fn test_unexpected_command() {
+        let mut transport = MockTransport::new();
+        transport.expect(Command::Current, vec![]);
+        let _ = UartBatteryModule::new(transport).request(Command::Temperature);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 expectation(s) not consumed")]
+    fn test_expectation_not_consumed() {
+        let mut transport = MockTransport::new();
+        transport.expect(Command::Current, vec![]);
+        transport.assert_done();
+    }
+}
@@ -1,12 +1,23 @@
 mod command;
 mod command_frame;
+mod consistency;
 mod data_frame;
+mod data_frame_encoder;
 mod data_frame_view;
 mod port;
+mod responder;
+mod transport;
+mod uart_battery_module;
 mod utils;
 
 pub use command::Command;
 pub use command_frame::CommandFrame;
+pub use consistency::{
+    resolution, sources, ConsistencyChecker, ConsistencyReport, Discrepancy, SourceValue,
+};
 pub use data_frame::DataFrame;
 pub use data_frame_view::DataFrameView;
 pub use port::Port;
+pub use responder::Responder;
+pub use transport::Transport;
+pub use uart_battery_module::UartBatteryModule;
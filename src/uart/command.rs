@@ -1,5 +1,16 @@
-#[derive(Clone, Copy, Debug)]
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::{
+    battery_module::BatteryField,
+    error::{Error, Result},
+};
+
+/// Serialized in snake case, e.g. `"summary_data"` or `"fail_status_1"`
+#[derive(Clone, Copy, Debug, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Command {
+    #[serde(rename = "fail_status_1")]
     FailStatus1 = 0x01,
     CellVoltage = 0x02,
     Current = 0x03,
@@ -7,6 +18,7 @@ pub enum Command {
     RemainingCapacity = 0x05,
     BmInformation = 0x10,
     FullChargeCapacity = 0x11,
+    #[serde(rename = "fail_status_2")]
     FailStatus2 = 0x13,
     StateOfHealth = 0x14,
     SummaryData = 0x20,
@@ -35,4 +47,91 @@ impl Command {
             Self::DesignCapacity => 2,
         }
     }
+
+    /// Returns the fields which the response to this command contains
+    pub fn fields(&self) -> &'static [BatteryField] {
+        use BatteryField::*;
+        match *self {
+            Self::FailStatus1 => &[FailStatus1],
+            Self::CellVoltage => &[CellVoltages],
+            Self::Current => &[Current],
+            Self::Temperature => &[Temperature],
+            Self::RemainingCapacity => &[RemainingCapacity],
+            Self::BmInformation => &[
+                CellVoltages,
+                Current,
+                Temperature,
+                RemainingCapacity,
+                FullChargeCapacity,
+                DesignCapacity,
+                AbsoluteStateOfCharge,
+                RelativeStateOfCharge,
+                StateOfHealth,
+                BmVoltage,
+                FailStatus1,
+                FailStatus2,
+            ],
+            Self::FullChargeCapacity => &[FullChargeCapacity],
+            Self::FailStatus2 => &[FailStatus2],
+            Self::StateOfHealth => &[StateOfHealth],
+            Self::SummaryData => &[
+                Current,
+                Temperature,
+                RemainingCapacity,
+                FullChargeCapacity,
+                DesignCapacity,
+                AbsoluteStateOfCharge,
+                RelativeStateOfCharge,
+                StateOfHealth,
+                BmVoltage,
+                FailStatus1,
+                FailStatus2,
+                FailStatus3,
+            ],
+            Self::VersionInformation => &[],
+            Self::DesignCapacity => &[DesignCapacity],
+        }
+    }
+}
+
+impl TryFrom<u8> for Command {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        Self::iter()
+            .find(|command| *command as u8 == value)
+            .ok_or(Error::UnknownCommand(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_from() {
+        for command in Command::iter() {
+            assert_eq!(Command::try_from(command as u8).unwrap(), command);
+        }
+        assert!(matches!(
+            Command::try_from(0x00),
+            Err(Error::UnknownCommand(0x00))
+        ));
+    }
+
+    #[test]
+    fn test_serde() {
+        assert_eq!(
+            serde_json::to_string(&Command::SummaryData).unwrap(),
+            r#""summary_data""#
+        );
+        assert_eq!(
+            serde_json::to_string(&Command::FailStatus1).unwrap(),
+            r#""fail_status_1""#
+        );
+        for command in Command::iter() {
+            let json = serde_json::to_string(&command).unwrap();
+            assert_eq!(serde_json::from_str::<Command>(&json).unwrap(), command);
+        }
+    }
 }
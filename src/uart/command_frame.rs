@@ -1,8 +1,18 @@
 use super::{command::Command, utils::checksum};
+use crate::error::{Error, Result};
 
-const COMMAND_FRAME_START_CODE: u8 = 0x05;
+pub(crate) const COMMAND_FRAME_START_CODE: u8 = 0x05;
 pub(crate) const LEADER_BM_ID: u8 = 0x01;
 
+const START_CODE_INDEX: usize = 0;
+const BM_ID_INDEX: usize = 1;
+const REQUEST_COMMAND_INDEX: usize = 2;
+const NUMBER_OF_DATA_INDEX: usize = 3;
+const CHECKSUM_INDEX: usize = 4;
+
+/// Length of a command frame without data (Start Code, BM ID, Request Command, Number of data, Checksum)
+pub(crate) const COMMAND_FRAME_LENGTH: usize = 5;
+
 #[derive(Debug)]
 pub struct CommandFrame {
     request_command: Command,
@@ -28,6 +38,42 @@ impl CommandFrame {
         }
     }
 
+    /// Parses and validates a command frame received from a host
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != COMMAND_FRAME_LENGTH {
+            return Err(Error::InvalidUartCommandFrame(format!(
+                "Invalid length (must be: {COMMAND_FRAME_LENGTH}, received {})",
+                bytes.len()
+            )));
+        }
+        if bytes[START_CODE_INDEX] != COMMAND_FRAME_START_CODE {
+            return Err(Error::InvalidUartCommandFrame(format!(
+                "Invalid start code (must be: {COMMAND_FRAME_START_CODE}, received {})",
+                bytes[START_CODE_INDEX]
+            )));
+        }
+        if bytes[BM_ID_INDEX] != LEADER_BM_ID {
+            return Err(Error::InvalidUartCommandFrame(format!(
+                "Invalid BM ID (must be: {LEADER_BM_ID}, received {})",
+                bytes[BM_ID_INDEX]
+            )));
+        }
+        if bytes[CHECKSUM_INDEX] != checksum(&bytes[..CHECKSUM_INDEX]) {
+            return Err(Error::InvalidUartCommandFrame(
+                "Invalid checksum".to_owned(),
+            ));
+        }
+        let request_command = Command::try_from(bytes[REQUEST_COMMAND_INDEX])?;
+        if bytes[NUMBER_OF_DATA_INDEX] as usize != request_command.number_of_data_in_command() {
+            return Err(Error::InvalidUartCommandFrame(format!(
+                "Invalid number of data (must be: {}, received {})",
+                request_command.number_of_data_in_command(),
+                bytes[NUMBER_OF_DATA_INDEX]
+            )));
+        }
+        Ok(Self::new(request_command))
+    }
+
     pub fn request_command(&self) -> Command {
         self.request_command
     }
@@ -38,3 +84,47 @@ impl AsRef<[u8]> for CommandFrame {
         &self.buf
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_from_bytes() {
+        let command_frame = CommandFrame::new(Command::SummaryData);
+        let parsed = CommandFrame::try_from_bytes(command_frame.as_ref()).unwrap();
+        assert_eq!(parsed.request_command(), Command::SummaryData);
+        assert_eq!(parsed.as_ref(), command_frame.as_ref());
+
+        // Invalid checksum
+        let mut bytes = command_frame.as_ref().to_vec();
+        bytes[CHECKSUM_INDEX] ^= 0xff;
+        let result = CommandFrame::try_from_bytes(&bytes);
+        assert!(result.unwrap_err().to_string().contains("Invalid checksum"));
+
+        // Invalid start code
+        let mut bytes = vec![0x02, LEADER_BM_ID, Command::Current as u8, 0x00];
+        bytes.push(checksum(&bytes));
+        let result = CommandFrame::try_from_bytes(&bytes);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid start code"));
+
+        // Invalid BM ID
+        let mut bytes = vec![COMMAND_FRAME_START_CODE, 0x02, Command::Current as u8, 0x00];
+        bytes.push(checksum(&bytes));
+        let result = CommandFrame::try_from_bytes(&bytes);
+        assert!(result.unwrap_err().to_string().contains("Invalid BM ID"));
+
+        // Unknown command
+        let mut bytes = vec![COMMAND_FRAME_START_CODE, LEADER_BM_ID, 0x7f, 0x00];
+        bytes.push(checksum(&bytes));
+        let result = CommandFrame::try_from_bytes(&bytes);
+        assert!(matches!(result, Err(Error::UnknownCommand(0x7f))));
+
+        // Invalid length
+        let result = CommandFrame::try_from_bytes(&command_frame.as_ref()[..4]);
+        assert!(result.unwrap_err().to_string().contains("Invalid length"));
+    }
+}
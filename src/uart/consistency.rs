@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use super::{Command, DataFrame, DataFrameView, Transport, UartBatteryModule};
+use crate::{battery_module::BatteryField, battery_state::BatteryState, error::Result};
+
+/// Returns the commands whose response contains `field`
+pub fn sources(field: BatteryField) -> Vec<Command> {
+    Command::iter()
+        .filter(|command| command.fields().contains(&field))
+        .collect()
+}
+
+/// Returns the smallest step of `field` in the response to `command`
+pub fn resolution(command: Command, field: BatteryField) -> f64 {
+    use BatteryField::*;
+    match (command, field) {
+        (Command::BmInformation | Command::SummaryData, Current) => 10.0,
+        (Command::Temperature, Temperature) => 1.0,
+        (_, Temperature) => 0.1,
+        (Command::SummaryData, RemainingCapacity | FullChargeCapacity | DesignCapacity) => 10.0,
+        _ => 1.0,
+    }
+}
+
+fn field_value(state: &(impl BatteryState + ?Sized), field: BatteryField) -> Result<Vec<f64>> {
+    use BatteryField::*;
+    Ok(match field {
+        CellVoltages => state
+            .cell_voltages()?
+            .into_iter()
+            .map(|voltage| voltage as f64)
+            .collect(),
+        Current => vec![state.current()? as f64],
+        Temperature => vec![state.temperature()?],
+        RemainingCapacity => vec![state.remaining_capacity()? as f64],
+        FullChargeCapacity => vec![state.full_charge_capacity()? as f64],
+        DesignCapacity => vec![state.design_capacity()? as f64],
+        AbsoluteStateOfCharge => vec![state.absolute_state_of_charge()? as f64],
+        RelativeStateOfCharge => vec![state.relative_state_of_charge()? as f64],
+        StateOfHealth => vec![state.state_of_health()? as f64],
+        BmVoltage => vec![state.bm_voltage()? as f64],
+        FailStatus1 => vec![state.fail_status_1()?.0 as f64],
+        FailStatus2 => vec![state.fail_status_2()?.0 as f64],
+        FailStatus3 => vec![state.fail_status_3()?.0 as f64],
+    })
+}
+
+/// A field decoded from the response to one command
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SourceValue {
+    pub command: Command,
+    /// One element per cell for `BatteryField::CellVoltages`, otherwise a single element
+    pub value: Vec<f64>,
+    pub resolution: f64,
+}
+
+/// Values of a field which differ by more than the tolerance between two commands
+/// A value is `None` if its command returned fewer values than the other, e.g. fewer cells.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Discrepancy {
+    pub field: BatteryField,
+    /// Index of the cell for `BatteryField::CellVoltages`, otherwise 0
+    pub index: usize,
+    /// The first command the field was read through
+    pub reference: (Command, Option<f64>),
+    pub other: (Command, Option<f64>),
+    pub tolerance: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConsistencyReport {
+    pub field: BatteryField,
+    pub values: Vec<SourceValue>,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl ConsistencyReport {
+    /// Returns whether the field was read through more than one command and all of them agree
+    pub fn is_consistent(&self) -> bool {
+        self.values.len() > 1 && self.discrepancies.is_empty()
+    }
+}
+
+/// Compares each value with the first one
+fn discrepancies(field: BatteryField, values: &[SourceValue], margin: f64) -> Vec<Discrepancy> {
+    let mut discrepancies = vec![];
+    if let Some((reference, others)) = values.split_first() {
+        for other in others {
+            let tolerance = reference.resolution.max(other.resolution) + margin;
+            for index in 0..reference.value.len().max(other.value.len()) {
+                let a = reference.value.get(index).copied();
+                let b = other.value.get(index).copied();
+                let differs = match (a, b) {
+                    (Some(a), Some(b)) => {
+                        (a - b).abs() > tolerance + f64::EPSILON * a.abs().max(b.abs())
+                    }
+                    _ => true,
+                };
+                if differs {
+                    discrepancies.push(Discrepancy {
+                        field,
+                        index,
+                        reference: (reference.command, a),
+                        other: (other.command, b),
+                        tolerance,
+                    });
+                }
+            }
+        }
+    }
+    discrepancies
+}
+
+/// Reads the same field through every command which provides it and compares the results
+///
+/// Values agree when they differ by at most the coarser resolution of the two commands,
+/// plus the margin set for the field. The commands are not answered at the same instant,
+/// so fields which move quickly, such as the current, may need a margin on a live module.
+#[derive(Clone, Debug, Default)]
+pub struct ConsistencyChecker {
+    margins: HashMap<BatteryField, f64>,
+}
+
+impl ConsistencyChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the difference allowed on top of the resolution for `field` (default: 0)
+    pub fn with_margin(mut self, field: BatteryField, margin: f64) -> Self {
+        self.margins.insert(field, margin);
+        self
+    }
+
+    /// Compares `field` decoded from the responses in `data_frames`
+    /// Responses which do not contain the field or fail to decode it are skipped.
+    pub fn compare(&self, field: BatteryField, data_frames: &[DataFrame]) -> ConsistencyReport {
+        let values: Vec<_> = data_frames
+            .iter()
+            .filter(|data_frame| data_frame.response_command().fields().contains(&field))
+            .filter_map(|data_frame| {
+                let view = DataFrameView::try_new(data_frame).ok()?;
+                let command = data_frame.response_command();
+                Some(SourceValue {
+                    command,
+                    value: field_value(&view, field).ok()?,
+                    resolution: resolution(command, field),
+                })
+            })
+            .collect();
+
+        let margin = self.margins.get(&field).copied().unwrap_or_default();
+        ConsistencyReport {
+            field,
+            discrepancies: discrepancies(field, &values, margin),
+            values,
+        }
+    }
+
+    /// Requests every command providing any of `fields` once and compares each field
+    pub fn check<T: Transport>(
+        &self,
+        module: &mut UartBatteryModule<T>,
+        fields: &[BatteryField],
+    ) -> Result<Vec<ConsistencyReport>> {
+        let mut data_frames = vec![];
+        for command in Command::iter()
+            .filter(|command| command.fields().iter().any(|field| fields.contains(field)))
+        {
+            data_frames.push(module.request(command)?);
+        }
+        Ok(fields
+            .iter()
+            .map(|field| self.compare(*field, &data_frames))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::{battery_snapshot::BatterySnapshot, FailStatus1, FailStatus2, FailStatus3};
+
+    fn snapshot() -> BatterySnapshot {
+        BatterySnapshot {
+            cell_voltages: Some(vec![3300; 8]),
+            current: Some(-12340),
+            temperature: Some(25.3),
+            remaining_capacity: Some(8000),
+            full_charge_capacity: Some(9500),
+            design_capacity: Some(10000),
+            absolute_state_of_charge: Some(80),
+            relative_state_of_charge: Some(84),
+            state_of_health: Some(95),
+            bm_voltage: Some(26400),
+            fail_status_1: Some(FailStatus1(0x40)),
+            fail_status_2: Some(FailStatus2(0x00)),
+            fail_status_3: Some(FailStatus3(0x00)),
+        }
+    }
+
+    struct EncodingTransport {
+        state: BatterySnapshot,
+        responses: VecDeque<u8>,
+    }
+
+    impl Transport for EncodingTransport {
+        fn send(&mut self, bytes: &[u8]) -> Result<()> {
+            let command = Command::try_from(bytes[2])?;
+            let data_frame = DataFrame::encode(command, &self.state)?;
+            self.responses.extend(data_frame.as_ref());
+            Ok(())
+        }
+
+        fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+            for byte in buf.iter_mut() {
+                *byte = self.responses.pop_front().unwrap();
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sources() {
+        assert_eq!(
+            sources(BatteryField::Current),
+            vec![
+                Command::Current,
+                Command::BmInformation,
+                Command::SummaryData
+            ]
+        );
+        assert_eq!(
+            sources(BatteryField::FailStatus3),
+            vec![Command::SummaryData]
+        );
+    }
+
+    #[test]
+    fn test_consistent_module() {
+        let mut module = UartBatteryModule::new(EncodingTransport {
+            state: snapshot(),
+            responses: VecDeque::new(),
+        });
+        let reports = ConsistencyChecker::new()
+            .check(
+                &mut module,
+                &[
+                    BatteryField::Current,
+                    BatteryField::Temperature,
+                    BatteryField::FullChargeCapacity,
+                    BatteryField::CellVoltages,
+                ],
+            )
+            .unwrap();
+        for report in &reports {
+            assert!(report.is_consistent(), "{report:?}");
+        }
+        assert_eq!(reports[0].values.len(), 3);
+        assert_eq!(reports[0].values[0].value, vec![-12340.0]);
+        assert_eq!(reports[0].values[1].value, vec![-12340.0]);
+    }
+
+    #[test]
+    fn test_discrepancy() {
+        let state = snapshot();
+        // 1 degC per bit
+        let temperature =
+            DataFrame::try_from_data(Command::Temperature, &25i16.to_be_bytes()).unwrap();
+        let data_frames = [
+            temperature,
+            DataFrame::encode(Command::BmInformation, &state).unwrap(),
+            DataFrame::encode(Command::SummaryData, &state).unwrap(),
+        ];
+        let report = ConsistencyChecker::new().compare(BatteryField::Temperature, &data_frames);
+        assert_eq!(report.values[0].value, vec![25.0]);
+        assert!(report.is_consistent());
+
+        // a module answering in 0.1 degC, like the other commands
+        let scaled = DataFrame::try_from_data(Command::Temperature, &253i16.to_be_bytes()).unwrap();
+        let report = ConsistencyChecker::new().compare(
+            BatteryField::Temperature,
+            &[
+                scaled,
+                DataFrame::encode(Command::BmInformation, &state).unwrap(),
+            ],
+        );
+        assert_eq!(report.discrepancies.len(), 1);
+        assert_eq!(
+            report.discrepancies[0].reference,
+            (Command::Temperature, Some(253.0))
+        );
+        assert_eq!(report.discrepancies[0].other.0, Command::BmInformation);
+
+        // 10 mA resolution plus the margin
+        let current =
+            DataFrame::try_from_data(Command::Current, &(-12400i16).to_be_bytes()).unwrap();
+        let checker = ConsistencyChecker::new().with_margin(BatteryField::Current, 50.0);
+        let report = checker.compare(
+            BatteryField::Current,
+            &[
+                current,
+                DataFrame::encode(Command::BmInformation, &state).unwrap(),
+            ],
+        );
+        assert!(report.is_consistent());
+
+        // a cell missing from one response
+        let source = |command, cells| SourceValue {
+            command,
+            value: vec![3300.0; cells],
+            resolution: 1.0,
+        };
+        let found = discrepancies(
+            BatteryField::CellVoltages,
+            &[
+                source(Command::CellVoltage, 8),
+                source(Command::BmInformation, 7),
+            ],
+            0.0,
+        );
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].index, 7);
+        assert_eq!(found[0].reference, (Command::CellVoltage, Some(3300.0)));
+        assert_eq!(found[0].other, (Command::BmInformation, None));
+    }
+}
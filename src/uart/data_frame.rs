@@ -28,6 +28,26 @@ impl DataFrame {
         }
     }
 
+    /// Builds a valid data frame which carries `data`
+    pub fn try_from_data(response_command: Command, data: &[u8]) -> Result<Self> {
+        if data.len() != response_command.number_of_data() {
+            return Err(Error::DataBytesShortage(format!(
+                "Invalid number of data (must be: {}, given {})",
+                response_command.number_of_data(),
+                data.len()
+            )));
+        }
+        let mut data_frame = Self::new(response_command);
+        data_frame.buf[START_CODE_INDEX] = DATA_FRAME_START_CODE;
+        data_frame.buf[BM_ID_INDEX] = LEADER_BM_ID;
+        data_frame.buf[RESPONSE_COMMAND_INDEX] = response_command as u8;
+        data_frame.buf[NUMBER_OF_DATA_INDEX] = data.len() as u8;
+        data_frame.buf[DATA_OFFSET..DATA_OFFSET + data.len()].copy_from_slice(data);
+        data_frame.buf[DATA_OFFSET + data.len()] =
+            checksum(&data_frame.buf[..DATA_OFFSET + data.len()]);
+        Ok(data_frame)
+    }
+
     pub fn data(&self) -> &[u8] {
         &self.buf[DATA_OFFSET..DATA_OFFSET + self.response_command.number_of_data()]
     }
@@ -71,6 +91,12 @@ impl DataFrame {
     }
 }
 
+impl AsRef<[u8]> for DataFrame {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
 impl AsMut<[u8]> for DataFrame {
     fn as_mut(&mut self) -> &mut [u8] {
         &mut self.buf
@@ -198,4 +224,17 @@ mod tests {
             .to_string()
             .contains("Invalid number of data"));
     }
+
+    #[test]
+    fn test_try_from_data() {
+        let data_frame = DataFrame::try_from_data(Command::Current, &[0x04, 0xd2]).unwrap();
+        assert!(data_frame.is_valid().is_ok());
+        assert_eq!(
+            data_frame.as_ref(),
+            &[0x02, 0x01, 0x03, 0x02, 0x04, 0xd2, 0xd4, 0x00][..]
+        );
+        assert_eq!(data_frame.data(), &[0x04, 0xd2][..]);
+
+        assert!(DataFrame::try_from_data(Command::Current, &[0x04]).is_err());
+    }
 }
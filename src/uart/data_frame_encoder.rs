@@ -0,0 +1,239 @@
+use super::{data_frame_view::*, Command, DataFrame};
+use crate::{
+    battery_module::BatteryField,
+    battery_state::BatteryState,
+    error::{Error, Result},
+};
+
+/// Returns the fields needed to encode the response to `command`
+pub fn encoded_fields(command: Command) -> &'static [BatteryField] {
+    use BatteryField::*;
+    match command {
+        // absolute/relative state of charge and BM voltage are derived from the others on decoding
+        Command::BmInformation => &[
+            CellVoltages,
+            Current,
+            Temperature,
+            RemainingCapacity,
+            FullChargeCapacity,
+            DesignCapacity,
+            StateOfHealth,
+            FailStatus1,
+            FailStatus2,
+        ],
+        _ => command.fields(),
+    }
+}
+
+fn saturating_i16(value: f64) -> [u8; 2] {
+    (value.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16).to_be_bytes()
+}
+
+fn saturating_u16(value: u32) -> [u8; 2] {
+    (value.min(u16::MAX as u32) as u16).to_be_bytes()
+}
+
+fn saturating_u8(value: u32) -> u8 {
+    value.min(u8::MAX as u32) as u8
+}
+
+fn put(data: &mut [u8], index: usize, bytes: &[u8]) {
+    data[index..index + bytes.len()].copy_from_slice(bytes);
+}
+
+fn cell_voltages_bytes(state: &(impl BatteryState + ?Sized)) -> Result<Vec<u8>> {
+    let cell_voltages = state.cell_voltages()?;
+    if cell_voltages.len() < NUMBER_OF_CELLS {
+        return Err(Error::DataBytesShortage(format!(
+            "Not enough cell voltages (must be: {NUMBER_OF_CELLS}, given {})",
+            cell_voltages.len()
+        )));
+    }
+    Ok(cell_voltages[..NUMBER_OF_CELLS]
+        .iter()
+        .flat_map(|voltage| saturating_u16(*voltage))
+        .collect())
+}
+
+impl DataFrame {
+    /// Encodes the response to `response_command` from `state`
+    /// This is the inverse of `DataFrameView`.
+    /// Values out of range of the frame format are saturated.
+    pub fn encode(response_command: Command, state: &(impl BatteryState + ?Sized)) -> Result<Self> {
+        let mut data = vec![0; response_command.number_of_data()];
+        match response_command {
+            Command::FailStatus1 => data[0] = state.fail_status_1()?.0,
+            Command::CellVoltage => data.copy_from_slice(&cell_voltages_bytes(state)?),
+            Command::Current => put(&mut data, 0, &saturating_i16(state.current()? as f64)),
+            Command::Temperature => put(&mut data, 0, &saturating_i16(state.temperature()?)),
+            Command::RemainingCapacity => {
+                put(&mut data, 0, &saturating_u16(state.remaining_capacity()?))
+            }
+            Command::BmInformation => {
+                data[FAIL_STATUS_1_INDEX_IN_BM_INFORMATION] = state.fail_status_1()?.0;
+                put(
+                    &mut data,
+                    CELL_VOLTAGE_INDEX_IN_BM_INFORMATION,
+                    &cell_voltages_bytes(state)?,
+                );
+                put(
+                    &mut data,
+                    CURRENT_INDEX_IN_BM_INFORMATION,
+                    &saturating_i16(state.current()? as f64 / 10.0),
+                );
+                put(
+                    &mut data,
+                    TEMPERATURE_INDEX_IN_BM_INFORMATION,
+                    &saturating_i16(state.temperature()? * 10.0),
+                );
+                put(
+                    &mut data,
+                    REMAINING_CAPACITY_INDEX_IN_BM_INFORMATION,
+                    &saturating_u16(state.remaining_capacity()?),
+                );
+                put(
+                    &mut data,
+                    FULL_CHARGE_CAPACITY_INDEX_IN_BM_INFORMATION,
+                    &saturating_u16(state.full_charge_capacity()?),
+                );
+                put(
+                    &mut data,
+                    DESIGN_CAPACITY_INDEX_IN_BM_INFORMATION,
+                    &saturating_u16(state.design_capacity()?),
+                );
+                data[FAIL_STATUS_2_INDEX_IN_BM_INFORMATION] = state.fail_status_2()?.0;
+                data[STATE_OF_HEALTH_INDEX_IN_BM_INFORMATION] =
+                    saturating_u8(state.state_of_health()?);
+            }
+            Command::FullChargeCapacity => {
+                put(&mut data, 0, &saturating_u16(state.full_charge_capacity()?))
+            }
+            Command::FailStatus2 => data[0] = state.fail_status_2()?.0,
+            Command::StateOfHealth => data[0] = saturating_u8(state.state_of_health()?),
+            Command::SummaryData => {
+                data[FAIL_STATUS_1_INDEX_IN_SUMMARY_DATA] = state.fail_status_1()?.0;
+                data[ABSOLUTE_STATE_OF_CHARGE_INDEX_IN_SUMMARY_DATA] =
+                    saturating_u8(state.absolute_state_of_charge()?);
+                data[RELATIVE_STATE_OF_CHARGE_INDEX_IN_SUMMARY_DATA] =
+                    saturating_u8(state.relative_state_of_charge()?);
+                data[STATE_OF_HEALTH_INDEX_IN_SUMMARY_DATA] =
+                    saturating_u8(state.state_of_health()?);
+                put(
+                    &mut data,
+                    CURRENT_INDEX_IN_SUMMARY_DATA,
+                    &saturating_i16(state.current()? as f64 / 10.0),
+                );
+                put(
+                    &mut data,
+                    BM_VOLTAGE_MAX_INDEX_IN_SUMMARY_DATA,
+                    &saturating_u16(state.bm_voltage()?),
+                );
+                data[FAIL_STATUS_2_INDEX_IN_SUMMARY_DATA] = state.fail_status_2()?.0;
+                data[FAIL_STATUS_3_INDEX_IN_SUMMARY_DATA] = state.fail_status_3()?.0;
+                put(
+                    &mut data,
+                    DESIGN_CAPACITY_INDEX_IN_SUMMARY_DATA,
+                    &saturating_u16((state.design_capacity()? + 5) / 10),
+                );
+                put(
+                    &mut data,
+                    FULL_CHARGE_CAPACITY_INDEX_IN_SUMMARY_DATA,
+                    &saturating_u16((state.full_charge_capacity()? + 5) / 10),
+                );
+                put(
+                    &mut data,
+                    REMAINING_CAPACITY_INDEX_IN_SUMMARY_DATA,
+                    &saturating_u16((state.remaining_capacity()? + 5) / 10),
+                );
+                let temperature = saturating_i16(state.temperature()? * 10.0);
+                put(
+                    &mut data,
+                    MAX_TEMPERATURE_INDEX_IN_SUMMARY_DATA,
+                    &temperature,
+                );
+                put(
+                    &mut data,
+                    MIN_TEMPERATURE_INDEX_IN_SUMMARY_DATA,
+                    &temperature,
+                );
+            }
+            Command::VersionInformation => return Err(Error::UnsupportedCommand(response_command)),
+            Command::DesignCapacity => put(&mut data, 0, &saturating_u16(state.design_capacity()?)),
+        }
+        Self::try_from_data(response_command, &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        battery_snapshot::BatterySnapshot, uart::DataFrameView, FailStatus1, FailStatus2,
+        FailStatus3,
+    };
+
+    fn snapshot() -> BatterySnapshot {
+        BatterySnapshot {
+            cell_voltages: Some(vec![3601, 3602, 3603, 3604, 3605, 3606, 3607, 3608]),
+            current: Some(-12340),
+            temperature: Some(25.0),
+            remaining_capacity: Some(8000),
+            full_charge_capacity: Some(9500),
+            design_capacity: Some(10000),
+            absolute_state_of_charge: Some(80),
+            relative_state_of_charge: Some(84),
+            state_of_health: Some(95),
+            bm_voltage: Some(28836),
+            fail_status_1: Some(FailStatus1(0x40)),
+            fail_status_2: Some(FailStatus2(0x08)),
+            fail_status_3: Some(FailStatus3(0x01)),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        use strum::IntoEnumIterator;
+
+        let snapshot = snapshot();
+        for command in Command::iter().filter(|command| !command.fields().is_empty()) {
+            let data_frame = DataFrame::encode(command, &snapshot).unwrap();
+            assert!(data_frame.is_valid().is_ok());
+            let view = DataFrameView::try_new(&data_frame).unwrap();
+            for &field in encoded_fields(command) {
+                let mut decoded = BatterySnapshot::default();
+                decoded.capture(&view, field).unwrap();
+                let mut expected = BatterySnapshot::default();
+                expected.capture(&snapshot, field).unwrap();
+                assert_eq!(decoded, expected, "{command:?} {field:?}");
+            }
+        }
+        assert!(matches!(
+            DataFrame::encode(Command::VersionInformation, &snapshot),
+            Err(Error::UnsupportedCommand(Command::VersionInformation))
+        ));
+    }
+
+    #[test]
+    fn test_encode_missing_field() {
+        let snapshot = BatterySnapshot {
+            current: Some(100),
+            ..Default::default()
+        };
+        assert!(DataFrame::encode(Command::Current, &snapshot).is_ok());
+        assert!(matches!(
+            DataFrame::encode(Command::SummaryData, &snapshot),
+            Err(Error::FieldNotAvailable(_))
+        ));
+    }
+
+    #[test]
+    fn test_encode_saturates() {
+        let snapshot = BatterySnapshot {
+            current: Some(-65000),
+            ..Default::default()
+        };
+        let data_frame = DataFrame::encode(Command::Current, &snapshot).unwrap();
+        let view = DataFrameView::try_new(&data_frame).unwrap();
+        assert_eq!(view.current().unwrap(), i16::MIN as i32);
+    }
+}
@@ -18,32 +18,31 @@ impl<'a> DataFrameView<'a> {
     }
 }
 
-const NUMBER_OF_CELLS: usize = 8; // on the assumption that the battery module is an `All-in-one type`
+pub(super) const NUMBER_OF_CELLS: usize = 8; // on the assumption that the battery module is an `All-in-one type`
 
-const CELL_VOLTAGE_INDEX_IN_BM_INFORMATION: usize = 1;
-const CURRENT_INDEX_IN_BM_INFORMATION: usize = 17;
-const TEMPERATURE_INDEX_IN_BM_INFORMATION: usize = 19;
-const REMAINING_CAPACITY_INDEX_IN_BM_INFORMATION: usize = 21;
-const FULL_CHARGE_CAPACITY_INDEX_IN_BM_INFORMATION: usize = 23;
-const DESIGN_CAPACITY_INDEX_IN_BM_INFORMATION: usize = 25;
-const STATE_OF_HEALTH_INDEX_IN_BM_INFORMATION: usize = 28;
-const FAIL_STATUS_1_INDEX_IN_BM_INFORMATION: usize = 0;
-const FAIL_STATUS_2_INDEX_IN_BM_INFORMATION: usize = 27;
+pub(super) const CELL_VOLTAGE_INDEX_IN_BM_INFORMATION: usize = 1;
+pub(super) const CURRENT_INDEX_IN_BM_INFORMATION: usize = 17;
+pub(super) const TEMPERATURE_INDEX_IN_BM_INFORMATION: usize = 19;
+pub(super) const REMAINING_CAPACITY_INDEX_IN_BM_INFORMATION: usize = 21;
+pub(super) const FULL_CHARGE_CAPACITY_INDEX_IN_BM_INFORMATION: usize = 23;
+pub(super) const DESIGN_CAPACITY_INDEX_IN_BM_INFORMATION: usize = 25;
+pub(super) const STATE_OF_HEALTH_INDEX_IN_BM_INFORMATION: usize = 28;
+pub(super) const FAIL_STATUS_1_INDEX_IN_BM_INFORMATION: usize = 0;
+pub(super) const FAIL_STATUS_2_INDEX_IN_BM_INFORMATION: usize = 27;
 
-const CURRENT_INDEX_IN_SUMMARY_DATA: usize = 7;
-const ABSOLUTE_STATE_OF_CHARGE_INDEX_IN_SUMMARY_DATA: usize = 2;
-const RELATIVE_STATE_OF_CHARGE_INDEX_IN_SUMMARY_DATA: usize = 3;
-const STATE_OF_HEALTH_INDEX_IN_SUMMARY_DATA: usize = 4;
-const BM_VOLTAGE_MAX_INDEX_IN_SUMMARY_DATA: usize = 11;
-const DESIGN_CAPACITY_INDEX_IN_SUMMARY_DATA: usize = 17;
-const FULL_CHARGE_CAPACITY_INDEX_IN_SUMMARY_DATA: usize = 19;
-const REMAINING_CAPACITY_INDEX_IN_SUMMARY_DATA: usize = 21;
-const MAX_TEMPERATURE_INDEX_IN_SUMMARY_DATA: usize = 32;
-#[allow(dead_code)]
-const MIN_TEMPERATURE_INDEX_IN_SUMMARY_DATA: usize = 35;
-const FAIL_STATUS_1_INDEX_IN_SUMMARY_DATA: usize = 0;
-const FAIL_STATUS_2_INDEX_IN_SUMMARY_DATA: usize = 13;
-const FAIL_STATUS_3_INDEX_IN_SUMMARY_DATA: usize = 14;
+pub(super) const CURRENT_INDEX_IN_SUMMARY_DATA: usize = 7;
+pub(super) const ABSOLUTE_STATE_OF_CHARGE_INDEX_IN_SUMMARY_DATA: usize = 2;
+pub(super) const RELATIVE_STATE_OF_CHARGE_INDEX_IN_SUMMARY_DATA: usize = 3;
+pub(super) const STATE_OF_HEALTH_INDEX_IN_SUMMARY_DATA: usize = 4;
+pub(super) const BM_VOLTAGE_MAX_INDEX_IN_SUMMARY_DATA: usize = 11;
+pub(super) const DESIGN_CAPACITY_INDEX_IN_SUMMARY_DATA: usize = 17;
+pub(super) const FULL_CHARGE_CAPACITY_INDEX_IN_SUMMARY_DATA: usize = 19;
+pub(super) const REMAINING_CAPACITY_INDEX_IN_SUMMARY_DATA: usize = 21;
+pub(super) const MAX_TEMPERATURE_INDEX_IN_SUMMARY_DATA: usize = 32;
+pub(super) const MIN_TEMPERATURE_INDEX_IN_SUMMARY_DATA: usize = 35;
+pub(super) const FAIL_STATUS_1_INDEX_IN_SUMMARY_DATA: usize = 0;
+pub(super) const FAIL_STATUS_2_INDEX_IN_SUMMARY_DATA: usize = 13;
+pub(super) const FAIL_STATUS_3_INDEX_IN_SUMMARY_DATA: usize = 14;
 
 impl<'a> BatteryState for DataFrameView<'a> {
     /// Returns voltages of each cells in the battery module
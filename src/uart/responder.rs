@@ -0,0 +1,271 @@
+use strum::IntoEnumIterator;
+
+use super::{
+    command_frame::{COMMAND_FRAME_LENGTH, COMMAND_FRAME_START_CODE},
+    data_frame_encoder::encoded_fields,
+    Command, CommandFrame, DataFrame, Transport,
+};
+use crate::{
+    battery_module::BatteryModule,
+    error::{Error, Result},
+};
+
+const DEFAULT_VERSION_INFORMATION: [u8; 3] = [0x01, 0x00, 0x00];
+
+/// Device side of the UART protocol
+///
+/// Reads command frames from `transport` and answers them with data frames
+/// encoded from the values `module` provides.
+/// This is the reverse of `Port` and can be used to build emulators and gateways.
+pub struct Responder<T, M> {
+    transport: T,
+    module: M,
+    version_information: [u8; 3],
+}
+
+impl<T: Transport, M: BatteryModule> Responder<T, M> {
+    pub fn new(transport: T, module: M) -> Self {
+        Self {
+            transport,
+            module,
+            version_information: DEFAULT_VERSION_INFORMATION,
+        }
+    }
+
+    /// Sets the bytes answered to `Command::VersionInformation`
+    pub fn with_version_information(mut self, version_information: [u8; 3]) -> Self {
+        self.version_information = version_information;
+        self
+    }
+
+    /// Returns the commands which can be answered with the fields `module` supports
+    pub fn supported_commands(&self) -> Vec<Command> {
+        Command::iter()
+            .filter(|command| self.is_supported(*command))
+            .collect()
+    }
+
+    pub fn is_supported(&self, command: Command) -> bool {
+        command == Command::VersionInformation
+            || encoded_fields(command)
+                .iter()
+                .all(|field| self.module.is_supported(*field))
+    }
+
+    /// Waits for a command frame
+    /// Bytes before a start code are skipped.
+    pub fn receive_command(&mut self) -> Result<Command> {
+        let mut bytes = [0; COMMAND_FRAME_LENGTH];
+        loop {
+            self.transport.receive(&mut bytes[..1])?;
+            if bytes[0] == COMMAND_FRAME_START_CODE {
+                break;
+            }
+        }
+        self.transport.receive(&mut bytes[1..])?;
+        Ok(CommandFrame::try_from_bytes(&bytes)?.request_command())
+    }
+
+    /// Builds the data frame answering `command`
+    pub fn response(&mut self, command: Command) -> Result<DataFrame> {
+        if command == Command::VersionInformation {
+            return DataFrame::try_from_data(command, &self.version_information);
+        }
+        if !self.is_supported(command) {
+            return Err(Error::UnsupportedCommand(command));
+        }
+        let snapshot = self.module.read(encoded_fields(command))?;
+        DataFrame::encode(command, &snapshot)
+    }
+
+    /// Answers `command`
+    pub fn respond(&mut self, command: Command) -> Result<()> {
+        let data_frame = self.response(command)?;
+        self.transport.send(data_frame.as_ref())
+    }
+
+    /// Waits for a command frame and answers it
+    /// Returns the answered command.
+    pub fn respond_once(&mut self) -> Result<Command> {
+        let command = self.receive_command()?;
+        self.respond(command)?;
+        Ok(command)
+    }
+
+    pub fn module(&self) -> &M {
+        &self.module
+    }
+
+    pub fn module_mut(&mut self) -> &mut M {
+        &mut self.module
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> (T, M) {
+        (self.transport, self.module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::VecDeque};
+
+    use super::*;
+    use crate::{
+        battery_module::BatteryField,
+        battery_snapshot::BatterySnapshot,
+        uart::{DataFrameView, UartBatteryModule},
+        BatteryState, FailStatus1, FailStatus2, FailStatus3,
+    };
+
+    /// Connects a host and a device in memory
+    #[derive(Default)]
+    struct Loopback {
+        to_device: VecDeque<u8>,
+        to_host: VecDeque<u8>,
+    }
+
+    struct Host<'a>(&'a RefCell<Loopback>);
+    struct Device<'a>(&'a RefCell<Loopback>);
+
+    fn pop(queue: &mut VecDeque<u8>, buf: &mut [u8]) -> Result<()> {
+        if queue.len() < buf.len() {
+            return Err(Error::UartFailedToReceive(
+                std::io::ErrorKind::TimedOut.into(),
+            ));
+        }
+        for byte in buf.iter_mut() {
+            *byte = queue.pop_front().unwrap();
+        }
+        Ok(())
+    }
+
+    impl Transport for Host<'_> {
+        fn send(&mut self, bytes: &[u8]) -> Result<()> {
+            self.0.borrow_mut().to_device.extend(bytes);
+            Ok(())
+        }
+
+        fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+            pop(&mut self.0.borrow_mut().to_host, buf)
+        }
+    }
+
+    impl Transport for Device<'_> {
+        fn send(&mut self, bytes: &[u8]) -> Result<()> {
+            self.0.borrow_mut().to_host.extend(bytes);
+            Ok(())
+        }
+
+        fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+            pop(&mut self.0.borrow_mut().to_device, buf)
+        }
+    }
+
+    fn snapshot() -> BatterySnapshot {
+        BatterySnapshot {
+            current: Some(-4200),
+            temperature: Some(28.0),
+            remaining_capacity: Some(5000),
+            full_charge_capacity: Some(9000),
+            design_capacity: Some(10000),
+            absolute_state_of_charge: Some(50),
+            relative_state_of_charge: Some(55),
+            state_of_health: Some(90),
+            bm_voltage: Some(28000),
+            fail_status_1: Some(FailStatus1(0x00)),
+            fail_status_2: Some(FailStatus2(0x00)),
+            fail_status_3: Some(FailStatus3(0x00)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_respond_once() {
+        let loopback = RefCell::new(Loopback::default());
+        let mut responder = Responder::new(Device(&loopback), snapshot());
+
+        let mut host = Host(&loopback);
+        host.send(CommandFrame::new(Command::SummaryData).as_ref())
+            .unwrap();
+        assert_eq!(responder.respond_once().unwrap(), Command::SummaryData);
+
+        let mut data_frame = DataFrame::new(Command::SummaryData);
+        host.receive(data_frame.as_mut()).unwrap();
+        let view = DataFrameView::try_new(&data_frame).unwrap();
+        assert_eq!(view.current().unwrap(), -4200);
+        assert_eq!(view.relative_state_of_charge().unwrap(), 55);
+        assert_eq!(view.temperature().unwrap(), 28.0);
+    }
+
+    #[test]
+    fn test_skip_garbage_before_start_code() {
+        let loopback = RefCell::new(Loopback::default());
+        let mut responder = Responder::new(Device(&loopback), snapshot());
+
+        let mut host = Host(&loopback);
+        host.send(&[0x00, 0xff, 0x13]).unwrap();
+        host.send(CommandFrame::new(Command::Current).as_ref())
+            .unwrap();
+        assert_eq!(responder.respond_once().unwrap(), Command::Current);
+    }
+
+    #[test]
+    fn test_unsupported_command() {
+        let loopback = RefCell::new(Loopback::default());
+        let mut responder = Responder::new(Device(&loopback), snapshot());
+
+        // cell voltages are not provided
+        assert!(!responder.is_supported(Command::CellVoltage));
+        assert!(!responder.is_supported(Command::BmInformation));
+        assert!(responder.is_supported(Command::VersionInformation));
+        assert!(matches!(
+            responder.respond(Command::CellVoltage),
+            Err(Error::UnsupportedCommand(Command::CellVoltage))
+        ));
+        assert!(loopback.borrow().to_host.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_with_port() {
+        use std::time::Duration;
+
+        use serialport::{SerialPort, TTYPort};
+
+        use crate::uart::Port;
+
+        let (mut master, slave) = TTYPort::pair().unwrap();
+        master.set_timeout(Duration::from_secs(1)).unwrap();
+        let path = slave.name().unwrap();
+        let device = std::thread::spawn(move || {
+            let mut responder = Responder::new(master, snapshot());
+            // serves until the host side is closed
+            let mut answered = vec![];
+            while let Ok(command) = responder.respond_once() {
+                answered.push(command);
+            }
+            answered
+        });
+
+        let port = Port::try_new(path, Duration::from_secs(1)).unwrap();
+        let mut module = UartBatteryModule::new(port);
+        let snapshot = module
+            .read(&[BatteryField::StateOfHealth, BatteryField::BmVoltage])
+            .unwrap();
+        assert_eq!(snapshot.state_of_health().unwrap(), 90);
+        assert_eq!(snapshot.bm_voltage().unwrap(), 28000);
+        let data_frame = module.request(Command::VersionInformation).unwrap();
+        assert_eq!(data_frame.data(), &DEFAULT_VERSION_INFORMATION[..]);
+
+        drop(module);
+        drop(slave);
+        assert_eq!(
+            device.join().unwrap(),
+            vec![Command::SummaryData, Command::VersionInformation]
+        );
+    }
+}
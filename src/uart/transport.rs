@@ -0,0 +1,72 @@
+use std::io::{Read, Write};
+
+use serialport::SerialPort;
+
+use super::Port;
+use crate::error::{Error, Result};
+
+/// Byte stream over which UART frames are exchanged
+pub trait Transport {
+    fn send(&mut self, bytes: &[u8]) -> Result<()>;
+    fn receive(&mut self, buf: &mut [u8]) -> Result<()>;
+}
+
+impl Transport for Port {
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        Port::send(self, &bytes)
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+        Port::receive(self, &mut &mut *buf)
+    }
+}
+
+fn write_all(writer: &mut (impl Write + ?Sized), bytes: &[u8]) -> Result<()> {
+    writer.write_all(bytes).map_err(Error::UartFailedToSend)?;
+    writer.flush().map_err(Error::UartFailedToSend)
+}
+
+fn read_exact(reader: &mut (impl Read + ?Sized), buf: &mut [u8]) -> Result<()> {
+    reader.read_exact(buf).map_err(Error::UartFailedToReceive)
+}
+
+impl Transport for dyn SerialPort {
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        write_all(self, bytes)
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+        read_exact(self, buf)
+    }
+}
+
+#[cfg(unix)]
+impl Transport for serialport::TTYPort {
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        write_all(self, bytes)
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+        read_exact(self, buf)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        (**self).send(bytes)
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+        (**self).receive(buf)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        (**self).send(bytes)
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+        (**self).receive(buf)
+    }
+}
@@ -0,0 +1,240 @@
+use super::{Command, CommandFrame, DataFrame, DataFrameView, Port, Transport};
+use crate::{
+    battery_module::{BatteryField, BatteryModule},
+    battery_snapshot::BatterySnapshot,
+    error::Result,
+};
+
+// Commands are tried in this order when planning a read.
+// Fields whose resolution or meaning differs in summary data are read through their own
+// command. Summary data then covers the rest in a single frame.
+const COMMAND_PRIORITY: [Command; 11] = [
+    Command::CellVoltage,
+    Command::Current,
+    Command::Temperature,
+    Command::RemainingCapacity,
+    Command::FullChargeCapacity,
+    Command::DesignCapacity,
+    Command::SummaryData,
+    Command::StateOfHealth,
+    Command::FailStatus1,
+    Command::FailStatus2,
+    Command::BmInformation,
+];
+
+/// `BatteryModule` which reads the battery module over UART
+///
+/// The current, the temperature and the capacities are always read through their own
+/// commands, at 1 mA and 1 mAh and as the temperature of the module, whatever else is read
+/// with them. The other fields are read from summary data.
+pub struct UartBatteryModule<T = Port> {
+    transport: T,
+}
+
+impl<T: Transport> UartBatteryModule<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Sends a command and returns the validated response
+    pub fn request(&mut self, command: Command) -> Result<DataFrame> {
+        self.transport.send(CommandFrame::new(command).as_ref())?;
+        let mut data_frame = DataFrame::new(command);
+        self.transport.receive(data_frame.as_mut())?;
+        data_frame.is_valid()?;
+        Ok(data_frame)
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+/// Returns commands to send and the fields to take from each response
+fn plan(fields: &[BatteryField]) -> Vec<(Command, Vec<BatteryField>)> {
+    let mut missing = fields.to_vec();
+    let mut plan = vec![];
+    for command in COMMAND_PRIORITY {
+        let (covered, rest): (Vec<_>, Vec<_>) = missing
+            .into_iter()
+            .partition(|field| command.fields().contains(field));
+        if !covered.is_empty() {
+            plan.push((command, covered));
+        }
+        missing = rest;
+    }
+    plan
+}
+
+impl<T: Transport> BatteryModule for UartBatteryModule<T> {
+    fn supported_fields(&self) -> Vec<BatteryField> {
+        use strum::IntoEnumIterator;
+        BatteryField::iter().collect()
+    }
+
+    fn read(&mut self, fields: &[BatteryField]) -> Result<BatterySnapshot> {
+        let mut snapshot = BatterySnapshot::default();
+        for (command, fields) in plan(fields) {
+            let data_frame = self.request(command)?;
+            let view = DataFrameView::try_new(&data_frame)?;
+            for field in fields {
+                snapshot.capture(&view, field)?;
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+impl<T: Transport> From<T> for UartBatteryModule<T> {
+    fn from(transport: T) -> Self {
+        Self::new(transport)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::{error::Error, uart::utils::checksum, BatteryState, FailStatus1};
+
+    struct CannedTransport {
+        sent: Vec<u8>,
+        responses: VecDeque<Vec<u8>>,
+    }
+
+    impl Transport for CannedTransport {
+        fn send(&mut self, bytes: &[u8]) -> Result<()> {
+            self.sent.extend_from_slice(bytes);
+            Ok(())
+        }
+
+        fn receive(&mut self, buf: &mut [u8]) -> Result<()> {
+            let response = self.responses.pop_front().unwrap();
+            buf.copy_from_slice(&response);
+            Ok(())
+        }
+    }
+
+    fn data_frame_bytes(command: Command, data: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0x02, 0x01, command as u8, data.len() as u8];
+        buf.extend_from_slice(data);
+        buf.push(checksum(&buf));
+        buf.push(0x00);
+        buf
+    }
+
+    #[test]
+    fn test_plan() {
+        use BatteryField::*;
+        assert_eq!(plan(&[Current]), vec![(Command::Current, vec![Current])]);
+        assert_eq!(
+            plan(&[CellVoltages, RelativeStateOfCharge]),
+            vec![
+                (Command::CellVoltage, vec![CellVoltages]),
+                (Command::SummaryData, vec![RelativeStateOfCharge]),
+            ]
+        );
+        // the current is read the same way however many fields are read with it
+        assert_eq!(
+            plan(&[Current, StateOfHealth, BmVoltage, FailStatus1]),
+            vec![
+                (Command::Current, vec![Current]),
+                (
+                    Command::SummaryData,
+                    vec![StateOfHealth, BmVoltage, FailStatus1]
+                ),
+            ]
+        );
+        assert_eq!(
+            plan(&[StateOfHealth]),
+            vec![(Command::SummaryData, vec![StateOfHealth])]
+        );
+        assert_eq!(plan(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_read() {
+        let mut summary_data = [0u8; 50];
+        summary_data[0] = 0x40; // fail status 1
+        summary_data[3] = 87; // relative state of charge
+        summary_data[7..9].copy_from_slice(&(-150i16).to_be_bytes()); // current (x10 mA)
+        let cell_voltage: Vec<u8> = (0..8u16).flat_map(|i| (3600 + i).to_be_bytes()).collect();
+
+        let transport = CannedTransport {
+            sent: vec![],
+            responses: VecDeque::from(vec![
+                data_frame_bytes(Command::CellVoltage, &cell_voltage),
+                data_frame_bytes(Command::Current, &(-1234i16).to_be_bytes()),
+                data_frame_bytes(Command::SummaryData, &summary_data),
+            ]),
+        };
+        let mut module = UartBatteryModule::new(transport);
+        let snapshot = module
+            .read(&[
+                BatteryField::Current,
+                BatteryField::CellVoltages,
+                BatteryField::RelativeStateOfCharge,
+                BatteryField::FailStatus1,
+            ])
+            .unwrap();
+
+        // at 1 mA, not from summary data
+        assert_eq!(snapshot.current().unwrap(), -1234);
+        assert_eq!(snapshot.relative_state_of_charge().unwrap(), 87);
+        assert_eq!(snapshot.fail_status_1().unwrap(), FailStatus1(0x40));
+        assert_eq!(
+            snapshot.cell_voltages().unwrap(),
+            vec![3600, 3601, 3602, 3603, 3604, 3605, 3606, 3607]
+        );
+        assert!(snapshot.temperature().is_err());
+
+        let expected_sent: Vec<u8> = [Command::CellVoltage, Command::Current, Command::SummaryData]
+            .into_iter()
+            .flat_map(|command| CommandFrame::new(command).as_ref().to_vec())
+            .collect();
+        assert_eq!(module.into_inner().sent, expected_sent);
+    }
+
+    #[test]
+    fn test_read_full_resolution() {
+        let transport = CannedTransport {
+            sent: vec![],
+            responses: VecDeque::from(vec![
+                data_frame_bytes(Command::Current, &(-1234i16).to_be_bytes()),
+                data_frame_bytes(Command::Temperature, &27i16.to_be_bytes()),
+                data_frame_bytes(Command::RemainingCapacity, &8765u16.to_be_bytes()),
+            ]),
+        };
+        let mut module = UartBatteryModule::new(transport);
+        let snapshot = module
+            .read(&[
+                BatteryField::Current,
+                BatteryField::Temperature,
+                BatteryField::RemainingCapacity,
+            ])
+            .unwrap();
+        assert_eq!(snapshot.current().unwrap(), -1234);
+        assert_eq!(snapshot.temperature().unwrap(), 27.0);
+        assert_eq!(snapshot.remaining_capacity().unwrap(), 8765);
+    }
+
+    #[test]
+    fn test_read_invalid_frame() {
+        let mut response = data_frame_bytes(Command::Current, &[0x04, 0xd2]);
+        response[6] ^= 0xff; // break checksum
+        let transport = CannedTransport {
+            sent: vec![],
+            responses: VecDeque::from(vec![response]),
+        };
+        let mut module = UartBatteryModule::new(transport);
+        assert!(matches!(
+            module.request(Command::Current),
+            Err(Error::InvalidUartDataFrame(_))
+        ));
+    }
+}